@@ -0,0 +1,1362 @@
+use std::{
+    fs,
+    io::Write,
+    mem,
+    ops::Range,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crossterm::{
+    event::{
+        KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+    },
+    style::Stylize,
+};
+
+use crate::{
+    bookmark::{parse_tags, Bookmark, Entry},
+    clipboard::Clipboard,
+    editor::Editor,
+    enter_tui, export,
+    filter::{Filter, Pattern},
+    form::Form,
+    history::{Change, History},
+    import,
+    layout::{Align, Truncate},
+    leave_tui,
+    opener::Opener,
+    prompt::Prompt,
+    render::{draw_field, draw_rows, draw_status, layout_rows, status_style},
+    screen::Screen,
+    search::{Direction, Query},
+    source::Source,
+    Error, Result,
+};
+
+/// Selected line and the first line and grapheme cluster of the viewport.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Cursor {
+    pub selected: usize,
+    pub scroll_pos: usize,
+    pub h_scroll: usize,
+}
+
+impl Cursor {
+    pub fn up(&mut self, count: usize) {
+        self.selected = self.selected.saturating_sub(count);
+    }
+
+    pub fn down(&mut self, count: usize) {
+        self.selected = self.selected.saturating_add(count);
+    }
+
+    /// Scroll the viewport without moving the selection unless it would leave the viewport.
+    pub fn scroll_down(&mut self, count: usize) {
+        self.scroll_pos = self.scroll_pos.saturating_add(count);
+        self.selected = self.selected.max(self.scroll_pos);
+    }
+
+    /// Scroll the viewport without moving the selection unless it would leave the viewport.
+    pub fn scroll_up(&mut self, count: usize, height: usize) {
+        self.scroll_pos = self.scroll_pos.saturating_sub(count);
+        self.selected = self
+            .selected
+            .min((self.scroll_pos + height).saturating_sub(1));
+    }
+
+    pub fn scroll_left(&mut self, count: usize) {
+        self.h_scroll = self.h_scroll.saturating_sub(count);
+    }
+
+    pub fn scroll_right(&mut self, count: usize) {
+        self.h_scroll = self.h_scroll.saturating_add(count);
+    }
+
+    /// Move both the selection and the viewport.
+    pub fn page_down(&mut self, count: usize) {
+        self.scroll_pos = self.scroll_pos.saturating_add(count);
+        self.selected = self.selected.saturating_add(count);
+    }
+
+    /// Move both the selection and the viewport.
+    pub fn page_up(&mut self, count: usize) {
+        self.scroll_pos = self.scroll_pos.saturating_sub(count);
+        self.selected = self.selected.saturating_sub(count);
+    }
+
+    /// Keep the selection within a list of the given length, and the viewport from scrolling
+    /// further than `overscroll` lines past the last page.
+    pub fn clamp(&mut self, len: usize, height: usize, overscroll: usize) {
+        let last = len.saturating_sub(1);
+        self.selected = self.selected.min(last);
+        self.scroll_pos = self
+            .scroll_pos
+            .min((len + overscroll).saturating_sub(height).min(last));
+    }
+
+    /// Scroll just enough for the selection to be visible in a viewport of the given height.
+    pub fn follow(&mut self, height: usize) {
+        if self.selected < self.scroll_pos {
+            self.scroll_pos = self.selected;
+        } else if self.selected >= self.scroll_pos + height {
+            self.scroll_pos = self.selected + 1 - height.max(1);
+        }
+    }
+
+    /// Selected row relative to the viewport.
+    pub fn selected_row(&self) -> usize {
+        self.selected - self.scroll_pos
+    }
+}
+
+#[derive(Debug, Default)]
+enum Mode {
+    #[default]
+    Normal,
+    /// Typing a search query, `origin` is where the search started and `previous` the query
+    /// active before it.
+    Search {
+        prompt: Prompt,
+        origin: Cursor,
+        previous: Option<Query>,
+    },
+    /// Typing a filter pattern, `origin` is the cursor and `previous` the filter active before it.
+    Filter {
+        prompt: Prompt,
+        origin: Cursor,
+        previous: Option<Filter>,
+    },
+    /// Typing a line number to go to.
+    GoTo { prompt: Prompt },
+    /// Filling in a bookmark, `line` is the line being edited or none for a new bookmark.
+    Form { form: Form, line: Option<usize> },
+    /// Typing the tags of the bookmark on `line`.
+    Retag {
+        prompt: Prompt,
+        line: usize,
+        bookmark: Bookmark,
+    },
+    /// Asking whether to delete a line.
+    ConfirmDelete { line: usize, label: String },
+    /// Typing the path of a file to import bookmarks from.
+    Import { prompt: Prompt },
+    /// Typing the path of a file to export the shown bookmarks to.
+    Export { prompt: Prompt },
+}
+
+/// Lines scrolled by a single step of the mouse wheel.
+const WHEEL_LINES: usize = 3;
+
+/// Longest time between two clicks on the same row for them to count as a double click.
+const DOUBLE_CLICK: Duration = Duration::from_millis(400);
+
+/// What the event loop should do after an event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flow {
+    Continue,
+    Quit,
+}
+
+/// Settings given on the command line.
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub opener: Opener,
+    /// Lines the viewport may scroll past the last page.
+    pub overscroll: usize,
+    pub align: Align,
+    pub truncate: Truncate,
+    /// Scroll the selected line back and forth when it does not fit.
+    pub marquee: bool,
+    /// Keep the previous version of the file when saving.
+    pub backup: bool,
+    pub editor: Editor,
+    pub clipboard: Clipboard,
+}
+
+pub struct App {
+    source: Source,
+    config: Config,
+    history: History,
+    cursor: Cursor,
+    size: (u16, u16),
+    screen: Screen,
+    message: Option<Error>,
+    /// Feedback shown when there is no error message.
+    info: Option<String>,
+    mode: Mode,
+    query: Option<Query>,
+    filter: Option<Filter>,
+    /// Cursor of the unfiltered list, put back when the filter is cleared.
+    unfiltered: Cursor,
+    /// First key of a key sequence such as "gg".
+    pending_key: Option<char>,
+    /// Time and line of the last left click.
+    last_click: Option<(Instant, usize)>,
+    /// Line shown on each row of the last display.
+    rows: Vec<usize>,
+    /// Line and step of the marquee, restarted when the selection moves.
+    marquee: (usize, usize),
+    /// Whether the selected line was too wide to be shown in full on the last display.
+    marquee_overflows: bool,
+    /// Selected row and entry as read while the file was unchanged, the selection is found again
+    /// from it after a reload as the old offsets may point into different lines by then.
+    anchor: Option<(usize, Entry)>,
+}
+
+impl App {
+    pub fn new(source: Source, history: History, config: Config, size: (u16, u16)) -> Self {
+        Self {
+            source,
+            history,
+            config,
+            cursor: Cursor::default(),
+            size,
+            screen: Screen::new(size),
+            message: None,
+            info: None,
+            mode: Mode::Normal,
+            query: None,
+            filter: None,
+            unfiltered: Cursor::default(),
+            pending_key: None,
+            last_click: None,
+            rows: Vec::new(),
+            marquee: (0, 0),
+            marquee_overflows: false,
+            anchor: None,
+        }
+    }
+
+    pub fn size(&self) -> (u16, u16) {
+        self.size
+    }
+
+    pub fn resize(&mut self, size: (u16, u16)) {
+        self.size = size;
+        self.screen.resize(size);
+    }
+
+    /// Handle background work, returns true if the display needs to be updated.
+    pub fn tick(&mut self) -> Result<bool> {
+        let marquee = self.config.marquee && self.marquee_overflows;
+        if marquee {
+            self.marquee.1 += 1;
+        }
+        let reload = self.source.changed_on_disk();
+        if reload {
+            self.message = self.reload().err();
+        }
+        Ok(self.source.poll_index()? || marquee || reload)
+    }
+
+    /// Read the file again after it changed on disk, keeping the selected entry on the same row
+    /// and edits in progress on the bookmark they started with.
+    fn reload(&mut self) -> Result<()> {
+        let anchor = self
+            .anchor
+            .take()
+            .filter(|(selected, _)| *selected == self.cursor.selected)
+            .map(|(_, entry)| entry);
+        let row = self.cursor.selected.saturating_sub(self.cursor.scroll_pos);
+
+        self.source.reload()?;
+        self.refilter()?;
+        self.info = Some("file changed on disk, reloaded".into());
+
+        if let Some(anchor) = anchor {
+            let near = self.cursor.selected;
+            let found = nearest_match(self.entries_from(0)?, &anchor, near)?;
+            if let Some(index) = found {
+                self.cursor.selected = index;
+                self.cursor.scroll_pos = index.saturating_sub(row);
+            }
+        }
+
+        let (line, bookmark) = match &self.mode {
+            Mode::Form {
+                form,
+                line: Some(line),
+            } => (*line, form.base().clone()),
+            Mode::Retag { line, bookmark, .. } => (*line, bookmark.clone()),
+            Mode::ConfirmDelete { .. } => {
+                self.mode = Mode::Normal;
+                return Ok(());
+            }
+            _ => return Ok(()),
+        };
+        let found = nearest_match(
+            self.source.entries_from(0)?,
+            &Entry::Bookmark(bookmark),
+            line,
+        )?;
+        match (&mut self.mode, found) {
+            (Mode::Form { line, .. }, Some(found)) => *line = Some(found),
+            (Mode::Retag { line, .. }, Some(found)) => *line = found,
+            // The bookmark is gone, an edited one is kept as a new bookmark.
+            (Mode::Form { form, line }, None) => {
+                *line = None;
+                if form.is_modified() {
+                    return Err(Error::EditConflict);
+                }
+            }
+            (_, None) => {
+                self.mode = Mode::Normal;
+                return Err(Error::EditConflict);
+            }
+            _ => (),
+        }
+
+        Ok(())
+    }
+
+    /// Length of the current view if known.
+    fn view_len(&self) -> Option<usize> {
+        match &self.filter {
+            Some(filter) => Some(filter.hits().len()),
+            None => self.source.line_count(),
+        }
+    }
+
+    /// Length of the current view, indexing the whole file if needed.
+    fn view_len_blocking(&mut self) -> Result<usize> {
+        match &self.filter {
+            Some(filter) => Ok(filter.hits().len()),
+            None => self.source.index_all(),
+        }
+    }
+
+    /// Rows available for entries, the last row is taken by the status line and a form takes
+    /// the rows above it.
+    fn view_height(&self) -> usize {
+        let form_rows = match self.mode {
+            Mode::Form { .. } => Form::ROWS,
+            _ => 0,
+        };
+        (self.size.1 as usize).saturating_sub(1 + form_rows)
+    }
+
+    pub fn display(&mut self, writer: impl Write) -> Result<()> {
+        let (width, height) = self.size;
+
+        // Indexing what will be shown lets the end of a file be found before its full index is.
+        if self.filter.is_none() {
+            self.source.index_to(
+                self.cursor
+                    .selected
+                    .max(self.cursor.scroll_pos + height as usize),
+            )?;
+        }
+        if let Some(len) = self.view_len() {
+            self.cursor
+                .clamp(len, self.view_height(), self.config.overscroll);
+        }
+        self.cursor.follow(self.view_height());
+
+        // Wrapped entries take up several rows, so the selection may still be cut off.
+        let size = (width, self.view_height() as u16);
+        let truncate = self.config.truncate;
+        if self.marquee.0 != self.cursor.selected {
+            self.marquee = (self.cursor.selected, 0);
+        }
+        let marquee_step = self.config.marquee.then_some(self.marquee.1);
+        let layout = loop {
+            let Cursor { h_scroll, .. } = self.cursor;
+            let marquee = marquee_step.map(|step| (self.cursor.selected_row(), step));
+            let layout = layout_rows(
+                self.entries_from(self.cursor.scroll_pos)?,
+                truncate,
+                h_scroll,
+                marquee,
+                size,
+            )?;
+            // Keep at least the last grapheme cluster of the longest line in view.
+            if self.cursor.h_scroll != 0 && self.cursor.h_scroll >= layout.longest {
+                self.cursor.h_scroll = layout.longest.saturating_sub(1);
+                continue;
+            }
+            if self.cursor.selected_row() < layout.complete || self.cursor.selected_row() == 0 {
+                break layout;
+            }
+            self.cursor.scroll_pos += 1;
+        };
+        self.marquee_overflows = layout.marquee_overflows;
+        let rows = layout.rows;
+
+        let query = self.query.clone();
+        let pattern = self.filter.as_ref().map(|filter| filter.pattern().clone());
+        draw_rows(
+            &mut self.screen,
+            &rows,
+            self.cursor.selected_row(),
+            |text| {
+                let mut ranges = Vec::new();
+                if let Some(query) = &query {
+                    ranges.extend(query.find_iter(text));
+                }
+                if let Some(fuzzy_match) = pattern.as_ref().and_then(|p| p.fuzzy_match(text)) {
+                    ranges.extend(fuzzy_match.ranges);
+                }
+                merge_ranges(ranges)
+            },
+            self.config.align,
+            size,
+        );
+        self.rows = rows
+            .iter()
+            .map(|row| self.cursor.scroll_pos + row.entry)
+            .collect();
+
+        if let Mode::Form { form, .. } = &self.mode {
+            for (i, (label, prompt, focused)) in form.fields().enumerate() {
+                let row = (self.view_height() + i) as u16;
+                draw_field(
+                    &mut self.screen,
+                    label,
+                    prompt,
+                    focused,
+                    row,
+                    width as usize,
+                );
+            }
+        }
+
+        let last_row = height.saturating_sub(1);
+        let mut cursor = None;
+        let (left, left_style) = if let Some((prefix, prompt)) = self.mode.prompt() {
+            cursor = Some(prefix.len_utf8() + prompt.cursor());
+            (format!("{prefix}{} ", prompt.text()), status_style())
+        } else if let Some(message) = &self.message {
+            (message.to_string(), status_style().red())
+        } else if let Some(info) = &self.info {
+            (info.clone(), status_style())
+        } else if let Mode::Form { line, .. } = self.mode {
+            let action = if line.is_some() { "save" } else { "add" };
+            (
+                format!("Tab: next field, Enter: {action}, Esc: cancel"),
+                status_style(),
+            )
+        } else if let Mode::ConfirmDelete { label, .. } = &self.mode {
+            (format!("delete {label}? (y/n)"), status_style())
+        } else {
+            (self.source.path().display().to_string(), status_style())
+        };
+        let right = self.status();
+        draw_status(
+            &mut self.screen,
+            &left,
+            left_style,
+            cursor,
+            &right,
+            last_row,
+            width as usize,
+        );
+
+        self.remember_selection()?;
+        self.screen.flush(writer)
+    }
+
+    /// Keep the selected entry to anchor the selection on if the file changes.
+    fn remember_selection(&mut self) -> Result<()> {
+        if !self.source.changed_on_disk() {
+            let entry = self.selected_entry()?;
+            self.anchor = entry.map(|entry| (self.cursor.selected, entry));
+        }
+        Ok(())
+    }
+
+    /// Active search and filter, and the position in the view.
+    fn status(&self) -> String {
+        let mut status = String::new();
+        if let Mode::Form { form, .. } = &self.mode {
+            if form.is_modified() {
+                status.push_str("[+]  ");
+            }
+        }
+        if let Some(query) = &self.query {
+            status.push_str(&format!("/{}  ", query.as_str()));
+        }
+        if let Some(filter) = &self.filter {
+            status.push_str(&format!(">{}  ", filter.pattern().as_str()));
+        }
+
+        match self.view_len() {
+            Some(0) => status.push_str("0/0"),
+            Some(len) => {
+                // Percentage of the lines past the first page that have been scrolled by.
+                let scrollable = len.saturating_sub(self.view_height());
+                let percent = (self.cursor.scroll_pos * 100)
+                    .checked_div(scrollable)
+                    .map_or(100, |percent| percent.min(100));
+                status.push_str(&format!("{}/{len} {percent}%", self.cursor.selected + 1));
+            }
+            None => status.push_str(&format!("{}/?", self.cursor.selected + 1)),
+        }
+
+        status
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> Result<Flow> {
+        self.message = None;
+        self.info = None;
+
+        if let KeyEvent {
+            code: KeyCode::Char('c'),
+            modifiers: KeyModifiers::CONTROL,
+            ..
+        } = key
+        {
+            return Ok(Flow::Quit);
+        }
+
+        if key.kind == KeyEventKind::Release {
+            return Ok(Flow::Continue);
+        }
+
+        match self.mode {
+            Mode::Normal => (),
+            Mode::Search { .. } => {
+                self.handle_search_key(key)?;
+                return Ok(Flow::Continue);
+            }
+            Mode::Filter { .. } => {
+                self.handle_filter_key(key)?;
+                return Ok(Flow::Continue);
+            }
+            Mode::GoTo { .. } => {
+                self.handle_goto_key(key)?;
+                return Ok(Flow::Continue);
+            }
+            Mode::Form { .. } => {
+                self.handle_form_key(key)?;
+                return Ok(Flow::Continue);
+            }
+            Mode::Retag { .. } => {
+                self.handle_retag_key(key)?;
+                return Ok(Flow::Continue);
+            }
+            Mode::Import { .. } | Mode::Export { .. } => {
+                self.handle_path_key(key)?;
+                return Ok(Flow::Continue);
+            }
+            Mode::ConfirmDelete { line, .. } => {
+                if key.code == KeyCode::Char('y') {
+                    self.message = self.delete_line(line).err();
+                }
+                self.mode = Mode::Normal;
+                return Ok(Flow::Continue);
+            }
+        }
+
+        let height = self.view_height();
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match (self.pending_key.take(), key.code) {
+            (_, KeyCode::Char('q')) => return Ok(Flow::Quit),
+            (_, KeyCode::Down | KeyCode::Char('j')) => self.cursor.down(1),
+            (_, KeyCode::Up | KeyCode::Char('k')) => self.cursor.up(1),
+            (_, KeyCode::PageDown) => self.cursor.page_down(height),
+            (_, KeyCode::PageUp) => self.cursor.page_up(height),
+            (_, KeyCode::Char('f')) if ctrl => self.cursor.page_down(height),
+            (_, KeyCode::Char('b')) if ctrl => self.cursor.page_up(height),
+            (_, KeyCode::Char('d')) if ctrl => self.cursor.page_down(height / 2),
+            (_, KeyCode::Char('u')) if ctrl => self.cursor.page_up(height / 2),
+            (_, KeyCode::Char('e')) if ctrl => self.cursor.scroll_down(1),
+            (_, KeyCode::Char('y')) if ctrl => self.cursor.scroll_up(1, height),
+            (_, KeyCode::Home) | (Some('g'), KeyCode::Char('g')) => self.cursor.selected = 0,
+            (_, KeyCode::Char('g')) => self.pending_key = Some('g'),
+            (_, KeyCode::Left) | (Some('z'), KeyCode::Char('h')) => self.cursor.scroll_left(1),
+            (_, KeyCode::Right) | (Some('z'), KeyCode::Char('l')) => self.cursor.scroll_right(1),
+            (_, KeyCode::Char('z')) => self.pending_key = Some('z'),
+            (_, KeyCode::End | KeyCode::Char('G')) => {
+                self.cursor.selected = self.view_len_blocking()?.saturating_sub(1)
+            }
+            (_, KeyCode::Char('A')) => {
+                self.config.align = self.config.align.next();
+                self.info = Some(format!("align: {}", self.config.align));
+            }
+            (_, KeyCode::Char('T')) => {
+                self.config.truncate = self.config.truncate.next();
+                self.info = Some(format!("truncate: {}", self.config.truncate));
+            }
+            (_, KeyCode::Char(':')) => {
+                self.mode = Mode::GoTo {
+                    prompt: Prompt::default(),
+                }
+            }
+            (_, KeyCode::Enter) => self.message = self.open_selected().err(),
+            (_, KeyCode::Char('/')) => {
+                self.mode = Mode::Search {
+                    prompt: Prompt::default(),
+                    origin: self.cursor,
+                    previous: self.query.take(),
+                }
+            }
+            (_, KeyCode::Char('n')) => self.message = self.search_next(Direction::Forward).err(),
+            (_, KeyCode::Char('N')) => self.message = self.search_next(Direction::Backward).err(),
+            (_, KeyCode::Char('f')) => {
+                if self.filter.is_none() {
+                    self.unfiltered = self.cursor;
+                }
+                self.mode = Mode::Filter {
+                    prompt: self
+                        .filter
+                        .as_ref()
+                        .map(|filter| Prompt::new(filter.pattern().as_str()))
+                        .unwrap_or_default(),
+                    origin: self.cursor,
+                    previous: self.filter.clone(),
+                }
+            }
+            (_, KeyCode::Esc) => self.clear_filter(),
+            (_, KeyCode::Char('a')) => {
+                self.mode = Mode::Form {
+                    form: Form::default(),
+                    line: None,
+                }
+            }
+            (_, KeyCode::Char('e')) => match self.selected_entry()? {
+                Some(Entry::Bookmark(bookmark)) => {
+                    self.mode = Mode::Form {
+                        form: Form::edit(bookmark),
+                        line: Some(self.selected_line()),
+                    }
+                }
+                _ => self.message = Some(Error::NotABookmark),
+            },
+            (Some('d'), KeyCode::Char('d')) => self.confirm_delete()?,
+            (_, KeyCode::Char('d')) => self.pending_key = Some('d'),
+            (_, KeyCode::Char('t')) => match self.selected_entry()? {
+                Some(Entry::Bookmark(bookmark)) => {
+                    self.mode = Mode::Retag {
+                        prompt: Prompt::new(&bookmark.tags.join(", ")),
+                        line: self.selected_line(),
+                        bookmark,
+                    }
+                }
+                _ => self.message = Some(Error::NotABookmark),
+            },
+            (_, KeyCode::Char('J')) => self.message = self.move_selected(Direction::Forward).err(),
+            (_, KeyCode::Char('K')) => self.message = self.move_selected(Direction::Backward).err(),
+            (_, KeyCode::Char('u')) => self.message = self.undo().err(),
+            (_, KeyCode::Char('y')) => self.message = self.copy_url().err(),
+            (_, KeyCode::Char('Y')) => self.message = self.copy_line().err(),
+            (_, KeyCode::Char('E')) => self.message = self.edit_selected_externally().err(),
+            (_, KeyCode::Char('v')) => self.message = self.edit_file_externally().err(),
+            (_, KeyCode::Char('r')) if ctrl => self.message = self.redo().err(),
+            (_, KeyCode::Char('I')) => {
+                self.mode = Mode::Import {
+                    prompt: Prompt::default(),
+                }
+            }
+            (_, KeyCode::Char('X')) => {
+                self.mode = Mode::Export {
+                    prompt: Prompt::default(),
+                }
+            }
+            _ => (),
+        }
+
+        Ok(Flow::Continue)
+    }
+
+    fn handle_search_key(&mut self, key: KeyEvent) -> Result<()> {
+        let Mode::Search {
+            prompt,
+            origin,
+            previous,
+        } = &mut self.mode
+        else {
+            return Ok(());
+        };
+        let origin = *origin;
+
+        match key.code {
+            KeyCode::Esc => {
+                self.query = previous.take();
+                self.cursor = origin;
+                self.mode = Mode::Normal;
+                return Ok(());
+            }
+            KeyCode::Enter => {
+                if self.query.is_none() {
+                    self.query = previous.take();
+                }
+                self.mode = Mode::Normal;
+                if let Some(query) = self.query.clone() {
+                    if !query.is_match(&self.selected_label()?) {
+                        self.message = Some(Error::NoMatch(query.as_str().into()));
+                    }
+                }
+                return Ok(());
+            }
+            // Removing from an empty prompt leaves it.
+            KeyCode::Backspace if prompt.text().is_empty() => {
+                self.query = previous.take();
+                self.cursor = origin;
+                self.mode = Mode::Normal;
+                return Ok(());
+            }
+            _ => {
+                let text = prompt.text().to_owned();
+                if !prompt.edit(key) || prompt.text() == text {
+                    return Ok(());
+                }
+            }
+        }
+
+        // Incremental search from where the search started.
+        self.query = Query::new(prompt.text());
+        self.cursor = origin;
+        if let Some(query) = self.query.clone() {
+            let found = query.find(self.entries_from(0)?, origin.selected, Direction::Forward)?;
+            if let Some(line) = found {
+                self.cursor.selected = line;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Handle a mouse event, returns true if the display needs to be updated.
+    pub fn handle_mouse(&mut self, mouse: MouseEvent) -> Result<bool> {
+        let height = self.view_height();
+        match mouse.kind {
+            MouseEventKind::ScrollDown => self.cursor.scroll_down(WHEEL_LINES),
+            MouseEventKind::ScrollUp => self.cursor.scroll_up(WHEEL_LINES, height),
+            MouseEventKind::Down(MouseButton::Left)
+                if matches!(self.mode, Mode::Normal) && (mouse.row as usize) < height =>
+            {
+                self.message = None;
+                self.info = None;
+                let Some(&line) = self.rows.get(mouse.row as usize) else {
+                    return Ok(false);
+                };
+                let now = Instant::now();
+                let is_double = self.last_click.is_some_and(|(time, last)| {
+                    last == line && now.duration_since(time) <= DOUBLE_CLICK
+                });
+
+                self.cursor.selected = line;
+                if is_double {
+                    self.last_click = None;
+                    self.message = self.open_selected().err();
+                } else {
+                    self.last_click = Some((now, line));
+                }
+            }
+            _ => return Ok(false),
+        }
+
+        Ok(true)
+    }
+
+    fn handle_goto_key(&mut self, key: KeyEvent) -> Result<()> {
+        let Mode::GoTo { prompt } = &mut self.mode else {
+            return Ok(());
+        };
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+
+        match key.code {
+            KeyCode::Esc => self.mode = Mode::Normal,
+            // Removing from an empty prompt leaves it.
+            KeyCode::Backspace if prompt.text().is_empty() => self.mode = Mode::Normal,
+            KeyCode::Char(c) if !c.is_ascii_digit() && !ctrl => (),
+            KeyCode::Enter => {
+                // Lines are numbered from 1 as in other editors, 0 goes to the first line.
+                match prompt.text().parse::<usize>() {
+                    Ok(line) => self.cursor.selected = line.saturating_sub(1),
+                    Err(_) => self.message = Some(Error::InvalidLine(prompt.text().into())),
+                }
+                self.mode = Mode::Normal;
+            }
+            _ => {
+                prompt.edit(key);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn handle_form_key(&mut self, key: KeyEvent) -> Result<()> {
+        let Mode::Form { form, line } = &mut self.mode else {
+            return Ok(());
+        };
+
+        match key.code {
+            KeyCode::Esc => self.mode = Mode::Normal,
+            KeyCode::Tab | KeyCode::Down => form.focus_next(),
+            KeyCode::BackTab | KeyCode::Up => form.focus_previous(),
+            // Saving an unchanged bookmark would only update its timestamp.
+            KeyCode::Enter if line.is_some() && !form.is_modified() => self.mode = Mode::Normal,
+            KeyCode::Enter => match form.bookmark() {
+                Ok(bookmark) => {
+                    let line = *line;
+                    self.message = self.save_bookmark(bookmark, line).err();
+                }
+                Err(error) => self.message = Some(Error::InvalidBookmark(error)),
+            },
+            _ => {
+                form.focused_mut().edit(key);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Write a bookmark over the given line or append it to the file, leaving the form.
+    fn save_bookmark(&mut self, mut bookmark: Bookmark, line: Option<usize>) -> Result<()> {
+        match line {
+            Some(line) => {
+                bookmark.modified = Some(unix_time());
+                self.change_lines(line..line + 1, vec![bookmark.to_string()])?;
+                self.info = Some(format!("saved {}", bookmark.label()));
+            }
+            None => {
+                bookmark.added = Some(unix_time());
+                let line = self.source.index_all()?;
+                self.change_lines(line..line, vec![bookmark.to_string()])?;
+                self.clear_filter();
+                self.cursor.selected = line;
+                self.info = Some(format!("added {}", bookmark.label()));
+            }
+        }
+
+        self.mode = Mode::Normal;
+        Ok(())
+    }
+
+    fn confirm_delete(&mut self) -> Result<()> {
+        if self.selected_entry()?.is_some() {
+            self.mode = Mode::ConfirmDelete {
+                line: self.selected_line(),
+                label: self.selected_label()?,
+            };
+        }
+        Ok(())
+    }
+
+    fn delete_line(&mut self, line: usize) -> Result<()> {
+        self.change_lines(line..line + 1, Vec::new())?;
+        self.info = Some(format!("deleted line {}", line + 1));
+        Ok(())
+    }
+
+    fn handle_retag_key(&mut self, key: KeyEvent) -> Result<()> {
+        let Mode::Retag {
+            prompt,
+            line,
+            bookmark,
+        } = &mut self.mode
+        else {
+            return Ok(());
+        };
+
+        match key.code {
+            KeyCode::Esc => self.mode = Mode::Normal,
+            KeyCode::Enter => {
+                let line = *line;
+                let bookmark = Bookmark {
+                    tags: parse_tags(prompt.text()),
+                    modified: Some(unix_time()),
+                    ..bookmark.clone()
+                };
+                self.mode = Mode::Normal;
+                self.message = self
+                    .change_lines(line..line + 1, vec![bookmark.to_string()])
+                    .err();
+            }
+            _ => {
+                prompt.edit(key);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Handle keys while typing the path to import from or export to.
+    fn handle_path_key(&mut self, key: KeyEvent) -> Result<()> {
+        let (Mode::Import { prompt } | Mode::Export { prompt }) = &mut self.mode else {
+            return Ok(());
+        };
+
+        match key.code {
+            KeyCode::Esc => self.mode = Mode::Normal,
+            KeyCode::Enter => {
+                let path = PathBuf::from(prompt.text());
+                let result = match mem::take(&mut self.mode) {
+                    Mode::Export { .. } => self.export(&path),
+                    _ => self.import(&path),
+                };
+                self.message = result.err();
+            }
+            _ => {
+                prompt.edit(key);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Add the bookmarks of a file to the end, selecting the first of them.
+    fn import(&mut self, path: &Path) -> Result<()> {
+        let bookmarks = import::read(None, path)?;
+        if bookmarks.is_empty() {
+            self.info = Some(format!("no bookmarks in {}", path.display()));
+            return Ok(());
+        }
+
+        let line = self.source.index_all()?;
+        let lines = bookmarks.iter().map(Bookmark::to_string).collect();
+        self.change_lines(line..line, lines)?;
+        self.clear_filter();
+        self.cursor.selected = line;
+        self.info = Some(format!("imported {} bookmarks", bookmarks.len()));
+        Ok(())
+    }
+
+    /// Write the shown bookmarks to a file, only the matching ones while filtering.
+    fn export(&mut self, path: &Path) -> Result<()> {
+        let mut bookmarks = Vec::new();
+        for entry in self.entries_from(0)? {
+            if let Entry::Bookmark(bookmark) = entry? {
+                bookmarks.push(bookmark);
+            }
+        }
+        export::write(None, path, self.source.path(), &bookmarks)?;
+        self.info = Some(format!(
+            "exported {} bookmarks to {}",
+            bookmarks.len(),
+            path.display()
+        ));
+        Ok(())
+    }
+
+    /// Swap the selected line with the one above or below it.
+    fn move_selected(&mut self, direction: Direction) -> Result<()> {
+        if self.filter.is_some() {
+            return Err(Error::MoveFiltered);
+        }
+
+        let line = self.cursor.selected;
+        let (first, target) = match direction {
+            Direction::Forward => (line, line + 1),
+            Direction::Backward if line > 0 => (line - 1, line - 1),
+            Direction::Backward => return Ok(()),
+        };
+        if first + 1 >= self.view_len_blocking()? {
+            return Ok(());
+        }
+
+        let mut lines = self.source.read_lines(first..first + 2)?;
+        lines.swap(0, 1);
+        self.change_lines(first..first + 2, lines)?;
+        self.cursor.selected = target;
+        Ok(())
+    }
+
+    fn copy_url(&mut self) -> Result<()> {
+        let Some(Entry::Bookmark(bookmark)) = self.selected_entry()? else {
+            return Err(Error::NotABookmark);
+        };
+        self.config.clipboard.copy(&bookmark.url)?;
+        self.info = Some(format!("copied {}", bookmark.url));
+        Ok(())
+    }
+
+    /// Copy the selected line as it is written in the file.
+    fn copy_line(&mut self) -> Result<()> {
+        if self.selected_entry()?.is_none() {
+            return Ok(());
+        }
+        let line = self.selected_line();
+        let Some(text) = self.source.read_lines(line..line + 1)?.pop() else {
+            return Ok(());
+        };
+        self.config.clipboard.copy(&text)?;
+        self.info = Some(format!("copied line {}", line + 1));
+        Ok(())
+    }
+
+    /// Hand the terminal over to the editor until it exits.
+    fn run_editor(&mut self, path: &Path, line: Option<usize>) -> Result<()> {
+        leave_tui();
+        let result = self.config.editor.edit(path, line);
+        enter_tui()?;
+        self.screen.invalidate();
+        result
+    }
+
+    /// Edit the selected line in the editor, it is replaced by the lines it is saved with.
+    fn edit_selected_externally(&mut self) -> Result<()> {
+        if self.selected_entry()?.is_none() {
+            return Ok(());
+        }
+        let line = self.selected_line();
+        let Some(old) = self.source.read_lines(line..line + 1)?.pop() else {
+            return Ok(());
+        };
+
+        // Removed when dropped, the ".txt" suffix lets editors pick a plain text mode.
+        let mut file = tempfile::Builder::new()
+            .prefix("bookmark-tui-")
+            .suffix(".txt")
+            .tempfile()?;
+        writeln!(file, "{old}")?;
+        self.run_editor(file.path(), None)?;
+
+        let new = fs::read_to_string(file.path())?
+            .split_terminator('\n')
+            .map(String::from)
+            .collect::<Vec<_>>();
+        if new != [old] {
+            self.change_lines(line..line + 1, new)?;
+            self.info = Some(format!("edited line {}", line + 1));
+        }
+        Ok(())
+    }
+
+    /// Edit the whole file in the editor starting at the selected line.
+    fn edit_file_externally(&mut self) -> Result<()> {
+        let path = self.source.path().to_owned();
+        // Editors saving in place leave the old offsets pointing into other lines.
+        self.remember_selection()?;
+        self.run_editor(&path, Some(self.selected_line()))?;
+        if self.source.changed_on_disk() {
+            self.reload()?;
+        }
+        Ok(())
+    }
+
+    /// Replace lines of the file, recording the change so that it can be undone.
+    fn change_lines(&mut self, range: Range<usize>, new: Vec<String>) -> Result<()> {
+        self.check_unchanged()?;
+        let old = self.source.read_lines(range.clone())?;
+        self.source
+            .replace_lines(range.clone(), &new, self.config.backup)?;
+        let result = self.history.record(Change {
+            line: range.start,
+            old,
+            new,
+        });
+        self.refilter()?;
+        result
+    }
+
+    /// Reload the file instead of changing it when another program changed it since the last
+    /// tick.
+    fn check_unchanged(&mut self) -> Result<()> {
+        if self.source.changed_on_disk() {
+            self.reload()?;
+            return Err(Error::EditConflict);
+        }
+        Ok(())
+    }
+
+    /// Apply a change from the history, the lines it replaces have to be unchanged.
+    fn apply(&mut self, change: &Change) -> Result<()> {
+        self.check_unchanged()?;
+        let range = change.line..change.line + change.old.len();
+        // Lines inserted past the end of a file that since got shorter have nothing to go after.
+        if change.line > self.source.index_all()?
+            || self.source.read_lines(range.clone())? != change.old
+        {
+            return Err(Error::HistoryConflict);
+        }
+        self.source
+            .replace_lines(range, &change.new, self.config.backup)?;
+        self.refilter()?;
+
+        if self.filter.is_none() {
+            self.cursor.selected = change.line;
+        }
+        Ok(())
+    }
+
+    fn undo(&mut self) -> Result<()> {
+        let Some(change) = self.history.last_undo().map(Change::inverse) else {
+            self.info = Some("nothing to undo".into());
+            return Ok(());
+        };
+        self.apply(&change)?;
+        self.history.undone()
+    }
+
+    fn redo(&mut self) -> Result<()> {
+        let Some(change) = self.history.last_redo().cloned() else {
+            self.info = Some("nothing to redo".into());
+            return Ok(());
+        };
+        self.apply(&change)?;
+        self.history.redone()
+    }
+
+    fn handle_filter_key(&mut self, key: KeyEvent) -> Result<()> {
+        let Mode::Filter {
+            prompt,
+            origin,
+            previous,
+        } = &mut self.mode
+        else {
+            return Ok(());
+        };
+
+        match key.code {
+            KeyCode::Enter => {
+                self.mode = Mode::Normal;
+                return Ok(());
+            }
+            KeyCode::Esc => {
+                self.filter = previous.take();
+                self.cursor = *origin;
+                self.mode = Mode::Normal;
+                return Ok(());
+            }
+            KeyCode::Backspace if prompt.text().is_empty() => {
+                self.filter = previous.take();
+                self.cursor = *origin;
+                self.mode = Mode::Normal;
+                return Ok(());
+            }
+            _ => {
+                let text = prompt.text().to_owned();
+                if !prompt.edit(key) || prompt.text() == text {
+                    return Ok(());
+                }
+            }
+        }
+
+        // An empty pattern shows the whole list again.
+        self.filter = Pattern::new(prompt.text())
+            .map(|pattern| Filter::new(pattern, &mut self.source))
+            .transpose()?;
+        self.cursor = if self.filter.is_some() {
+            Cursor::default()
+        } else {
+            self.unfiltered
+        };
+
+        Ok(())
+    }
+
+    /// Match the active filter against the file again after it changed.
+    fn refilter(&mut self) -> Result<()> {
+        if let Some(filter) = self.filter.take() {
+            self.filter = Some(Filter::new(filter.pattern().clone(), &mut self.source)?);
+        }
+        Ok(())
+    }
+
+    fn clear_filter(&mut self) {
+        if self.filter.take().is_some() {
+            self.cursor = self.unfiltered;
+        }
+    }
+
+    /// Entries of the current view starting at the given row.
+    fn entries_from(&mut self, row: usize) -> Result<Box<dyn Iterator<Item = Result<Entry>> + '_>> {
+        Ok(match &self.filter {
+            Some(filter) => Box::new(
+                filter
+                    .hits()
+                    .iter()
+                    .skip(row)
+                    .map(|hit| Ok(hit.entry.clone())),
+            ),
+            None => Box::new(self.source.entries_from(row)?),
+        })
+    }
+
+    /// Line in the file of the selected entry.
+    fn selected_line(&self) -> usize {
+        match &self.filter {
+            Some(filter) => filter
+                .hits()
+                .get(self.cursor.selected)
+                .map_or(self.cursor.selected, |hit| hit.line),
+            None => self.cursor.selected,
+        }
+    }
+
+    fn selected_entry(&mut self) -> Result<Option<Entry>> {
+        self.entries_from(self.cursor.selected)?.next().transpose()
+    }
+
+    fn search_next(&mut self, direction: Direction) -> Result<()> {
+        let Some(query) = self.query.clone() else {
+            return Ok(());
+        };
+
+        // Searching backwards from past the end wraps around to the last match.
+        let from = match direction {
+            Direction::Forward => self.cursor.selected + 1,
+            Direction::Backward => self.cursor.selected.checked_sub(1).unwrap_or(usize::MAX),
+        };
+
+        let found = query.find(self.entries_from(0)?, from, direction)?;
+        match found {
+            Some(line) => {
+                self.cursor.selected = line;
+                Ok(())
+            }
+            None => Err(Error::NoMatch(query.as_str().into())),
+        }
+    }
+
+    fn selected_label(&mut self) -> Result<String> {
+        Ok(self
+            .selected_entry()?
+            .map(|entry| entry.label().into_owned())
+            .unwrap_or_default())
+    }
+
+    fn open_selected(&mut self) -> Result<()> {
+        match self.selected_entry()? {
+            Some(Entry::Bookmark(bookmark)) => self.config.opener.open(&bookmark.url),
+            _ => Err(Error::NotABookmark),
+        }
+    }
+}
+
+impl Mode {
+    /// Prefix and prompt of modes reading text input.
+    fn prompt(&self) -> Option<(char, &Prompt)> {
+        match self {
+            Mode::Normal | Mode::Form { .. } | Mode::ConfirmDelete { .. } => None,
+            Mode::Search { prompt, .. } => Some(('/', prompt)),
+            Mode::Filter { prompt, .. } => Some(('>', prompt)),
+            Mode::GoTo { prompt } => Some((':', prompt)),
+            Mode::Retag { prompt, .. } => Some(('#', prompt)),
+            Mode::Import { prompt } => Some(('<', prompt)),
+            Mode::Export { prompt } => Some(('@', prompt)),
+        }
+    }
+}
+
+/// Index of the entry equal to the given one closest to `near`.
+fn nearest_match(
+    entries: impl IntoIterator<Item = Result<Entry>>,
+    entry: &Entry,
+    near: usize,
+) -> Result<Option<usize>> {
+    let mut nearest = None::<usize>;
+    for (index, candidate) in entries.into_iter().enumerate() {
+        // Entries further down only get further away.
+        if nearest.is_some_and(|nearest| index > near && index - near >= near.abs_diff(nearest)) {
+            break;
+        }
+        if candidate? == *entry {
+            nearest = Some(index);
+        }
+    }
+    Ok(nearest)
+}
+
+/// Current time in unix seconds.
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs())
+}
+
+/// Sort ranges and merge the ones that overlap.
+fn merge_ranges(mut ranges: Vec<Range<usize>>) -> Vec<Range<usize>> {
+    ranges.sort_unstable_by_key(|range| range.start);
+    let mut merged = Vec::<Range<usize>>::with_capacity(ranges.len());
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::OpenOptions, io};
+
+    use super::*;
+
+    fn app(path: &Path) -> App {
+        let config = Config {
+            opener: Opener::new("true").unwrap(),
+            overscroll: 0,
+            align: Align::default(),
+            truncate: Truncate::default(),
+            marquee: false,
+            backup: false,
+            editor: Editor::from_env(),
+            clipboard: Clipboard::Terminal,
+        };
+        let source = Source::open(path).unwrap();
+        App::new(source, History::default(), config, (40, 10))
+    }
+
+    fn key(app: &mut App, c: char) {
+        let key = KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+        app.handle_key(key).unwrap();
+        app.display(io::sink()).unwrap();
+    }
+
+    fn selected_url(app: &mut App) -> String {
+        match app.selected_entry().unwrap() {
+            Some(Entry::Bookmark(bookmark)) => bookmark.url,
+            entry => panic!("expected a bookmark, got {entry:?}"),
+        }
+    }
+
+    /// Select the second of three bookmarks, change the file with `rewrite` and reload it.
+    fn reload_after(rewrite: impl FnOnce(&Path, &str)) -> App {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bookmarks.txt");
+        fs::write(&path, "https://a\nhttps://b\nhttps://c\n").unwrap();
+
+        let mut app = app(&path);
+        app.display(io::sink()).unwrap();
+        key(&mut app, 'j');
+        assert_eq!(selected_url(&mut app), "https://b");
+
+        rewrite(&path, "https://new\nhttps://a\nhttps://b\nhttps://c\n");
+        assert!(app.tick().unwrap());
+        app
+    }
+
+    #[test]
+    fn selection_is_kept_when_the_file_is_rewritten_in_place() {
+        let mut app = reload_after(|path, text| {
+            let mut file = OpenOptions::new()
+                .write(true)
+                .truncate(true)
+                .open(path)
+                .unwrap();
+            file.write_all(text.as_bytes()).unwrap();
+        });
+        assert_eq!(app.cursor.selected, 2);
+        assert_eq!(selected_url(&mut app), "https://b");
+    }
+
+    #[test]
+    fn selection_is_kept_when_the_file_is_replaced() {
+        let mut app = reload_after(|path, text| {
+            let new = path.with_extension("new");
+            fs::write(&new, text).unwrap();
+            fs::rename(new, path).unwrap();
+        });
+        assert_eq!(app.cursor.selected, 2);
+        assert_eq!(selected_url(&mut app), "https://b");
+    }
+
+    #[test]
+    fn export_does_not_replace_the_bookmark_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bookmarks.txt");
+        fs::write(&path, "https://a\n").unwrap();
+        let mut app = app(&path);
+
+        let same = dir.path().join(".").join("bookmarks.txt");
+        assert!(matches!(app.export(&same), Err(Error::ExportOverSource(_))));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "https://a\n");
+
+        app.export(&dir.path().join("bookmarks.html")).unwrap();
+    }
+}
@@ -0,0 +1,393 @@
+use std::{
+    borrow::Cow,
+    fmt::{self, Display, Write as _},
+    str::FromStr,
+};
+
+use thiserror::Error;
+
+/// Separator between the fields of a bookmark line.
+const FIELD_SEP: char = '\t';
+
+/// Separator between tags in the tag field.
+const TAG_SEP: char = ',';
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    #[error("missing url")]
+    MissingUrl,
+    #[error("url \"{0}\" has no scheme")]
+    NoScheme(String),
+    #[error("url contains whitespace")]
+    UrlWhitespace,
+    #[error("too many fields, expected at most {expected} found {found}")]
+    TooManyFields { expected: usize, found: usize },
+    #[error("invalid escape sequence \"\\{0}\"")]
+    InvalidEscape(char),
+    #[error("field ends with a lone backslash")]
+    TrailingBackslash,
+    #[error("invalid {field} timestamp \"{value}\"")]
+    InvalidTimestamp { field: &'static str, value: String },
+    #[error("invalid flags \"{0}\"")]
+    InvalidFlags(String),
+}
+
+/// A single bookmark.
+///
+/// Bookmarks are stored one per line as tab separated fields in the order
+/// url, title, description, tags, added, modified, category, icon, keyword and flags. Only the url
+/// is required, trailing fields may be left out. Tags are separated by commas, timestamps are unix
+/// seconds and categories are folder paths separated by slashes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Bookmark {
+    pub url: String,
+    pub title: String,
+    pub description: String,
+    pub tags: Vec<String>,
+    pub added: Option<u64>,
+    pub modified: Option<u64>,
+    pub category: String,
+    /// Url of the favicon, usually a data url.
+    pub icon: String,
+    /// Short name typed in the address bar of a browser to open the bookmark.
+    pub keyword: String,
+    /// Flags of the bookmark in buku, written as a number when not zero.
+    pub flags: u64,
+}
+
+impl Bookmark {
+    const FIELD_COUNT: usize = 10;
+
+    /// Bookmark with the given fields, `tags` is a comma separated list.
+    pub fn new(url: &str, title: &str, tags: &str) -> Result<Self, ParseError> {
+        validate_url(url)?;
+        Ok(Self {
+            url: url.into(),
+            title: title.into(),
+            tags: parse_tags(tags),
+            ..Self::default()
+        })
+    }
+
+    /// Text used when showing the bookmark in a list.
+    pub fn label(&self) -> Cow<'_, str> {
+        if self.title.is_empty() {
+            Cow::Borrowed(&self.url)
+        } else {
+            Cow::Owned(format!("{} <{}>", self.title, self.url))
+        }
+    }
+}
+
+impl FromStr for Bookmark {
+    type Err = ParseError;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let fields = line.split(FIELD_SEP).collect::<Vec<_>>();
+        if fields.len() > Self::FIELD_COUNT {
+            return Err(ParseError::TooManyFields {
+                expected: Self::FIELD_COUNT,
+                found: fields.len(),
+            });
+        }
+
+        let mut fields = fields.into_iter().map(unescape);
+        let mut next = || fields.next().transpose().map(Option::unwrap_or_default);
+
+        let url = next()?;
+        let title = next()?;
+        let description = next()?;
+        let tags = next()?;
+        let added = next()?;
+        let modified = next()?;
+        let category = next()?;
+        let icon = next()?;
+        let keyword = next()?;
+        let flags = next()?;
+
+        validate_url(&url)?;
+
+        Ok(Self {
+            url,
+            title,
+            description,
+            tags: parse_tags(&tags),
+            added: parse_timestamp("added", &added)?,
+            modified: parse_timestamp("modified", &modified)?,
+            category,
+            icon,
+            keyword,
+            flags: match flags.as_str() {
+                "" => 0,
+                flags => flags
+                    .parse()
+                    .map_err(|_| ParseError::InvalidFlags(flags.into()))?,
+            },
+        })
+    }
+}
+
+impl Display for Bookmark {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let tags = self.tags.join(&TAG_SEP.to_string());
+        let added = self.added.map(|t| t.to_string()).unwrap_or_default();
+        let modified = self.modified.map(|t| t.to_string()).unwrap_or_default();
+        let flags = match self.flags {
+            0 => String::new(),
+            flags => flags.to_string(),
+        };
+
+        let fields = [
+            self.url.as_str(),
+            &self.title,
+            &self.description,
+            &tags,
+            &added,
+            &modified,
+            &self.category,
+            &self.icon,
+            &self.keyword,
+            &flags,
+        ];
+
+        // Trailing empty fields are left out to keep lines short.
+        let used = fields
+            .iter()
+            .rposition(|field| !field.is_empty())
+            .map_or(1, |i| i + 1);
+
+        for (i, field) in fields[..used].iter().enumerate() {
+            if i != 0 {
+                f.write_char(FIELD_SEP)?;
+            }
+            write_escaped(f, field)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A line of a bookmark file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Entry {
+    Bookmark(Bookmark),
+    /// Blank lines and lines starting with '#'.
+    Comment(String),
+    /// Lines that could not be parsed, kept as is so that nothing is lost.
+    Invalid {
+        line: String,
+        error: ParseError,
+    },
+}
+
+impl Entry {
+    /// Parse a line, a trailing line break is ignored.
+    pub fn parse(line: &str) -> Self {
+        let line = line
+            .strip_suffix('\n')
+            .map(|line| line.strip_suffix('\r').unwrap_or(line))
+            .unwrap_or(line);
+
+        if line.trim().is_empty() || line.starts_with('#') {
+            return Self::Comment(line.into());
+        }
+
+        match line.parse() {
+            Ok(bookmark) => Self::Bookmark(bookmark),
+            Err(error) => Self::Invalid {
+                line: line.into(),
+                error,
+            },
+        }
+    }
+
+    /// Text used when showing the entry in a list.
+    pub fn label(&self) -> Cow<'_, str> {
+        match self {
+            Self::Bookmark(bookmark) => bookmark.label(),
+            Self::Comment(line) => Cow::Borrowed(line),
+            Self::Invalid { line, error } => Cow::Owned(format!("{line} [{error}]")),
+        }
+    }
+}
+
+impl Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Bookmark(bookmark) => bookmark.fmt(f),
+            Self::Comment(line) | Self::Invalid { line, .. } => f.write_str(line),
+        }
+    }
+}
+
+fn validate_url(url: &str) -> Result<(), ParseError> {
+    if url.is_empty() {
+        return Err(ParseError::MissingUrl);
+    }
+
+    if url.contains(char::is_whitespace) {
+        return Err(ParseError::UrlWhitespace);
+    }
+
+    let has_scheme = url.split_once(':').is_some_and(|(scheme, _)| {
+        let mut chars = scheme.chars();
+        chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+            && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+    });
+
+    if has_scheme {
+        Ok(())
+    } else {
+        Err(ParseError::NoScheme(url.into()))
+    }
+}
+
+/// Split a comma separated list of tags.
+pub fn parse_tags(tags: &str) -> Vec<String> {
+    tags.split(TAG_SEP)
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+fn parse_timestamp(field: &'static str, value: &str) -> Result<Option<u64>, ParseError> {
+    if value.is_empty() {
+        return Ok(None);
+    }
+
+    value
+        .parse()
+        .map(Some)
+        .map_err(|_| ParseError::InvalidTimestamp {
+            field,
+            value: value.into(),
+        })
+}
+
+fn unescape(field: &str) -> Result<String, ParseError> {
+    if !field.contains('\\') {
+        return Ok(field.into());
+    }
+
+    let mut out = String::with_capacity(field.len());
+    let mut chars = field.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+
+        out.push(match chars.next() {
+            Some('\\') => '\\',
+            Some('t') => '\t',
+            Some('n') => '\n',
+            Some('r') => '\r',
+            Some(c) => return Err(ParseError::InvalidEscape(c)),
+            None => return Err(ParseError::TrailingBackslash),
+        });
+    }
+
+    Ok(out)
+}
+
+fn write_escaped(f: &mut impl fmt::Write, field: &str) -> fmt::Result {
+    for c in field.chars() {
+        match c {
+            '\\' => f.write_str("\\\\")?,
+            '\t' => f.write_str("\\t")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            c => f.write_char(c)?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_and_writes_every_field() {
+        let line =
+            "https://example.com\tTitle\tline\\none\\ttab \\\\\tb,a\t1\t2\tWork/Sub\tdata:,\tex\t5";
+        let bookmark = line.parse::<Bookmark>().unwrap();
+        assert_eq!(
+            bookmark,
+            Bookmark {
+                url: "https://example.com".into(),
+                title: "Title".into(),
+                description: "line\none\ttab \\".into(),
+                tags: vec!["b".into(), "a".into()],
+                added: Some(1),
+                modified: Some(2),
+                category: "Work/Sub".into(),
+                icon: "data:,".into(),
+                keyword: "ex".into(),
+                flags: 5,
+            }
+        );
+        assert_eq!(bookmark.to_string(), line);
+    }
+
+    #[test]
+    fn trailing_empty_fields_are_left_out() {
+        let bookmark = "https://example.com\t\tdesc\t\t\t"
+            .parse::<Bookmark>()
+            .unwrap();
+        assert_eq!(bookmark.description, "desc");
+        assert_eq!(bookmark.to_string(), "https://example.com\t\tdesc");
+
+        let bookmark = Bookmark::new("https://example.com", "", " a, ,b ").unwrap();
+        assert_eq!(bookmark.tags, ["a", "b"]);
+        assert_eq!(bookmark.to_string(), "https://example.com\t\t\ta,b");
+    }
+
+    #[test]
+    fn invalid_lines_are_rejected() {
+        let error = |line: &str| line.parse::<Bookmark>().unwrap_err();
+        assert_eq!(error(""), ParseError::MissingUrl);
+        assert_eq!(
+            error("example.com"),
+            ParseError::NoScheme("example.com".into())
+        );
+        assert_eq!(error("1http://a"), ParseError::NoScheme("1http://a".into()));
+        assert_eq!(error("http://a b"), ParseError::UrlWhitespace);
+        assert_eq!(
+            error("http://a\t\t\t\t\t\t\t\t\t\t"),
+            ParseError::TooManyFields {
+                expected: 10,
+                found: 11
+            }
+        );
+        assert_eq!(
+            error("http://a\t\t\t\tyesterday"),
+            ParseError::InvalidTimestamp {
+                field: "added",
+                value: "yesterday".into()
+            }
+        );
+        assert_eq!(
+            error("http://a\t\t\t\t\t\t\t\t\t-1"),
+            ParseError::InvalidFlags("-1".into())
+        );
+    }
+
+    #[test]
+    fn unescape_handles_escapes() {
+        assert_eq!(unescape("plain").unwrap(), "plain");
+        assert_eq!(unescape("a\\tb\\nc\\rd\\\\e").unwrap(), "a\tb\nc\rd\\e");
+        assert_eq!(unescape("a\\x"), Err(ParseError::InvalidEscape('x')));
+        assert_eq!(unescape("a\\"), Err(ParseError::TrailingBackslash));
+    }
+
+    #[test]
+    fn entries_keep_comments_and_invalid_lines() {
+        assert_eq!(Entry::parse("# note\n"), Entry::Comment("# note".into()));
+        assert_eq!(Entry::parse("  \r\n"), Entry::Comment("  ".into()));
+        let entry = Entry::parse("not a url\r\n");
+        assert!(matches!(&entry, Entry::Invalid { line, .. } if line == "not a url"));
+        assert_eq!(entry.to_string(), "not a url");
+    }
+}
@@ -0,0 +1,135 @@
+use std::path::Path;
+
+use rusqlite::{params, Connection, OpenFlags};
+
+use crate::{
+    bookmark::{parse_tags, Bookmark},
+    Result,
+};
+
+/// Table buku keeps bookmarks in, as buku creates it.
+const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS bookmarks (
+    id integer PRIMARY KEY,
+    URL text NOT NULL UNIQUE,
+    metadata text default '',
+    tags text default ',',
+    desc text default '',
+    flags integer default 0
+)";
+
+/// Read the bookmarks of a buku database, in the order they were added.
+pub fn read(path: &Path) -> Result<Vec<Bookmark>> {
+    let db = Connection::open_with_flags(
+        path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )?;
+    let mut statement =
+        db.prepare("SELECT URL, metadata, tags, desc, flags FROM bookmarks ORDER BY id")?;
+    let rows = statement.query_map([], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, Option<String>>(1)?.unwrap_or_default(),
+            row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+            row.get::<_, Option<String>>(3)?.unwrap_or_default(),
+            row.get::<_, Option<u64>>(4)?.unwrap_or_default(),
+        ))
+    })?;
+
+    let mut bookmarks = Vec::new();
+    for row in rows {
+        let (url, title, tags, description, flags) = row?;
+        // Tags are stored with a comma at both ends, such as ",news,rust,".
+        let Ok(bookmark) = Bookmark::new(&url, &title, "") else {
+            continue;
+        };
+        bookmarks.push(Bookmark {
+            description,
+            tags: parse_tags(&tags),
+            flags,
+            ..bookmark
+        });
+    }
+    Ok(bookmarks)
+}
+
+/// Add bookmarks to a buku database, creating it if it does not exist. Bookmarks already in it
+/// are updated, matched by their url.
+pub fn write(path: &Path, bookmarks: &[Bookmark]) -> Result<()> {
+    let mut db = Connection::open(path)?;
+    db.execute(SCHEMA, [])?;
+
+    let transaction = db.transaction()?;
+    {
+        let mut statement = transaction.prepare(
+            "INSERT INTO bookmarks (URL, metadata, tags, desc, flags) VALUES (?1, ?2, ?3, ?4, ?5)
+            ON CONFLICT (URL) DO UPDATE SET metadata = excluded.metadata,
+                tags = excluded.tags, desc = excluded.desc, flags = excluded.flags",
+        )?;
+        for bookmark in bookmarks {
+            let tags = format!(",{},", bookmark.tags.join(","));
+            statement.execute(params![
+                bookmark.url,
+                bookmark.title,
+                if bookmark.tags.is_empty() { "," } else { &tags },
+                bookmark.description,
+                bookmark.flags,
+            ])?;
+        }
+    }
+    transaction.commit()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tags_are_stored_with_commas_at_both_ends() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bookmarks.db");
+        let bookmarks = [
+            Bookmark {
+                description: "desc".into(),
+                flags: 2,
+                ..Bookmark::new("https://a.example/", "A", "a,b").unwrap()
+            },
+            Bookmark::new("https://b.example/", "", "").unwrap(),
+        ];
+        write(&path, &bookmarks).unwrap();
+
+        let db = Connection::open(&path).unwrap();
+        let tags = db
+            .prepare("SELECT tags FROM bookmarks ORDER BY id")
+            .unwrap()
+            .query_map([], |row| row.get::<_, String>(0))
+            .unwrap()
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(tags, [",a,b,", ","]);
+
+        assert_eq!(read(&path).unwrap(), bookmarks);
+    }
+
+    #[test]
+    fn bookmarks_with_the_same_url_are_updated() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bookmarks.db");
+        write(
+            &path,
+            &[Bookmark::new("https://a.example/", "Old", "old").unwrap()],
+        )
+        .unwrap();
+
+        let updated = [
+            Bookmark::new("https://b.example/", "B", "").unwrap(),
+            Bookmark::new("https://a.example/", "New", "new").unwrap(),
+        ];
+        write(&path, &updated).unwrap();
+        // The updated bookmark keeps its place in the database.
+        assert_eq!(
+            read(&path).unwrap(),
+            [updated[1].clone(), updated[0].clone()]
+        );
+    }
+}
@@ -0,0 +1,369 @@
+use std::{fmt::Write as _, fs, io::Write, path::Path};
+
+use md5::{Digest, Md5};
+use serde_json::{json, Map, Value};
+
+use crate::{
+    bookmark::{parse_tags, Bookmark},
+    folder::{category_path, Folder, Item},
+    Error, Result,
+};
+
+/// Seconds from 1601-01-01, where Chromium timestamps start, to the unix epoch.
+const EPOCH_OFFSET: u64 = 11_644_473_600;
+
+/// Roots of the bookmark tree in the order they are written, with their names, ids and guids.
+/// Bookmarks of the root for other bookmarks have no category.
+const ROOTS: [Root; 3] = [
+    Root {
+        key: "bookmark_bar",
+        name: "Bookmarks bar",
+        guid: "0bc5d13f-2cba-5d74-951f-3f233fe6c908",
+    },
+    Root {
+        key: "other",
+        name: "Other bookmarks",
+        guid: "82b081ec-3dd3-529c-8475-ab6c344590dd",
+    },
+    Root {
+        key: "synced",
+        name: "Mobile bookmarks",
+        guid: "4cf2e351-0e85-532b-bb37-df045d8f8d0f",
+    },
+];
+
+/// Index of the root for other bookmarks in [ROOTS].
+const OTHER: usize = 1;
+
+/// Keys of `meta_info` keeping fields Chromium has no place for.
+const META_DESCRIPTION: &str = "description";
+const META_TAGS: &str = "tags";
+const META_KEYWORD: &str = "keyword";
+const META_FLAGS: &str = "flags";
+
+struct Root {
+    key: &'static str,
+    name: &'static str,
+    guid: &'static str,
+}
+
+/// Read the bookmarks of a Chromium `Bookmarks` file, folders become categories.
+pub fn read(path: &Path) -> Result<Vec<Bookmark>> {
+    let json = serde_json::from_slice::<Value>(&fs::read(path)?)?;
+    let roots = json.get("roots").ok_or(Error::NotChromium)?;
+
+    let mut bookmarks = Vec::new();
+    for (i, root) in ROOTS.iter().enumerate() {
+        let Some(node) = roots.get(root.key) else {
+            continue;
+        };
+        let mut path = Vec::new();
+        if i != OTHER {
+            path.push(root.name.to_string());
+        }
+        collect(children(node), &mut path, &mut bookmarks);
+    }
+    Ok(bookmarks)
+}
+
+fn children(node: &Value) -> &[Value] {
+    node.get("children")
+        .and_then(Value::as_array)
+        .map_or(&[], Vec::as_slice)
+}
+
+fn collect(nodes: &[Value], path: &mut Vec<String>, bookmarks: &mut Vec<Bookmark>) {
+    for node in nodes {
+        let name = node.get("name").and_then(Value::as_str).unwrap_or_default();
+        match node.get("type").and_then(Value::as_str) {
+            Some("folder") => {
+                path.push(name.into());
+                collect(children(node), path, bookmarks);
+                path.pop();
+            }
+            Some("url") => {
+                let url = node.get("url").and_then(Value::as_str).unwrap_or_default();
+                let Ok(bookmark) = Bookmark::new(url, name, "") else {
+                    continue;
+                };
+                let meta = |key| {
+                    node.get("meta_info")
+                        .and_then(|meta| meta.get(key))
+                        .and_then(Value::as_str)
+                        .unwrap_or_default()
+                };
+                let added = node
+                    .get("date_added")
+                    .and_then(Value::as_str)
+                    .and_then(|t| t.parse::<u64>().ok())
+                    .and_then(|t| (t / 1_000_000).checked_sub(EPOCH_OFFSET));
+
+                bookmarks.push(Bookmark {
+                    description: meta(META_DESCRIPTION).into(),
+                    tags: parse_tags(meta(META_TAGS)),
+                    added,
+                    category: path.join("/"),
+                    keyword: meta(META_KEYWORD).into(),
+                    flags: meta(META_FLAGS).parse().unwrap_or_default(),
+                    ..bookmark
+                });
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Write bookmarks as a Chromium `Bookmarks` file, categories become folders.
+///
+/// Categories starting with the name of the bookmarks bar or mobile bookmarks are placed in them,
+/// the others in other bookmarks. Descriptions, tags, keywords and flags are kept in `meta_info`,
+/// icons and modification times are left out.
+pub fn write(out: &mut impl Write, bookmarks: &[Bookmark]) -> Result<()> {
+    let mut trees = ROOTS.map(|_| Folder::default());
+    for bookmark in bookmarks {
+        let mut path = category_path(&bookmark.category).peekable();
+        let root = path
+            .peek()
+            .and_then(|name| ROOTS.iter().position(|root| root.name == *name))
+            .filter(|&i| i != OTHER);
+        if root.is_some() {
+            path.next();
+        }
+        trees[root.unwrap_or(OTHER)].insert(path, bookmark);
+    }
+
+    let mut encoder = Encoder {
+        next_id: ROOTS.len() + 1,
+        checksum: Md5::new(),
+    };
+    let mut roots = Map::new();
+    for (i, (root, tree)) in ROOTS.iter().zip(&trees).enumerate() {
+        let id = (i + 1).to_string();
+        encoder.update_folder(&id, root.name);
+        let children = encoder.encode_items(&tree.items);
+        roots.insert(
+            root.key.into(),
+            json!({
+                "children": children,
+                "date_added": "0",
+                "date_modified": "0",
+                "guid": root.guid,
+                "id": id,
+                "name": root.name,
+                "type": "folder",
+            }),
+        );
+    }
+
+    let json = json!({
+        "checksum": encoder.checksum(),
+        "roots": roots,
+        "version": 1,
+    });
+    serde_json::to_writer_pretty(&mut *out, &json)?;
+    writeln!(out)?;
+    Ok(())
+}
+
+/// Assigns ids to the nodes being written while computing the checksum of the file.
+struct Encoder {
+    next_id: usize,
+    checksum: Md5,
+}
+
+impl Encoder {
+    fn encode_items(&mut self, items: &[Item]) -> Vec<Value> {
+        items.iter().map(|item| self.encode(item)).collect()
+    }
+
+    fn encode(&mut self, item: &Item) -> Value {
+        let id = self.next_id.to_string();
+        self.next_id += 1;
+
+        match item {
+            Item::Folder(folder) => {
+                self.update_folder(&id, folder.name);
+                json!({
+                    "children": self.encode_items(&folder.items),
+                    "date_added": "0",
+                    "date_modified": "0",
+                    "guid": guid(&id, folder.name),
+                    "id": id,
+                    "name": folder.name,
+                    "type": "folder",
+                })
+            }
+            Item::Bookmark(bookmark) => {
+                self.update_url(&id, &bookmark.title, &bookmark.url);
+                let added = bookmark.added.map_or(0, |t| (t + EPOCH_OFFSET) * 1_000_000);
+                let mut node = json!({
+                    "date_added": added.to_string(),
+                    "date_last_used": "0",
+                    "guid": guid(&id, &bookmark.url),
+                    "id": id,
+                    "name": bookmark.title,
+                    "type": "url",
+                    "url": bookmark.url,
+                });
+
+                let tags = bookmark.tags.join(",");
+                let flags = match bookmark.flags {
+                    0 => String::new(),
+                    flags => flags.to_string(),
+                };
+                let meta = [
+                    (META_DESCRIPTION, bookmark.description.as_str()),
+                    (META_TAGS, &tags),
+                    (META_KEYWORD, &bookmark.keyword),
+                    (META_FLAGS, &flags),
+                ]
+                .into_iter()
+                .filter(|(_, value)| !value.is_empty())
+                .map(|(key, value)| (key.to_string(), Value::from(value)))
+                .collect::<Map<_, _>>();
+                if !meta.is_empty() {
+                    node["meta_info"] = meta.into();
+                }
+                node
+            }
+        }
+    }
+
+    // The checksum covers the id, title and type of every node and the urls of bookmarks, with
+    // titles as UTF-16 as Chromium keeps them.
+
+    fn update_folder(&mut self, id: &str, name: &str) {
+        self.checksum.update(id);
+        self.update_title(name);
+        self.checksum.update("folder");
+    }
+
+    fn update_url(&mut self, id: &str, title: &str, url: &str) {
+        self.checksum.update(id);
+        self.update_title(title);
+        self.checksum.update("url");
+        self.checksum.update(url);
+    }
+
+    fn update_title(&mut self, title: &str) {
+        for unit in title.encode_utf16() {
+            self.checksum.update(unit.to_le_bytes());
+        }
+    }
+
+    fn checksum(self) -> String {
+        hex(&self.checksum.finalize())
+    }
+}
+
+/// Guid for a node, derived from its id and name so that exports of the same bookmarks match.
+fn guid(id: &str, name: &str) -> String {
+    let mut bytes = Md5::new().chain_update(id).chain_update(name).finalize();
+    // Shaped as a version 4 uuid, which Chromium expects.
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex = hex(&bytes);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut out, byte| {
+        let _ = write!(out, "{byte:02x}");
+        out
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(bookmarks: &[Bookmark]) -> Vec<Bookmark> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("Bookmarks");
+        let mut json = Vec::new();
+        write(&mut json, bookmarks).unwrap();
+        fs::write(&path, json).unwrap();
+        read(&path).unwrap()
+    }
+
+    #[test]
+    fn export_and_import_give_the_same_bookmarks() {
+        let bookmarks = vec![
+            Bookmark {
+                url: "https://example.com/".into(),
+                title: " Spaced & \"quoted\" ".into(),
+                description: "desc\nline".into(),
+                tags: vec!["rust".into(), "two words".into()],
+                added: Some(1_680_000_000),
+                category: "Bookmarks bar/Dev".into(),
+                keyword: "ex".into(),
+                flags: 3,
+                ..Bookmark::default()
+            },
+            Bookmark {
+                url: "https://example.org/".into(),
+                category: "Work/Sub".into(),
+                ..Bookmark::default()
+            },
+            Bookmark {
+                url: "https://example.net/".into(),
+                title: "Phone".into(),
+                category: "Mobile bookmarks".into(),
+                ..Bookmark::default()
+            },
+        ];
+
+        // Bookmarks are read back root by root, other bookmarks coming after the bookmarks bar.
+        assert_eq!(round_trip(&bookmarks), bookmarks);
+    }
+
+    #[test]
+    fn checksum_covers_ids_titles_and_urls() {
+        let bookmarks = [
+            Bookmark {
+                url: "https://a.example/".into(),
+                title: "Ünï 😀".into(),
+                category: "Bookmarks bar/Dev".into(),
+                ..Bookmark::default()
+            },
+            Bookmark {
+                url: "https://b.example/".into(),
+                ..Bookmark::default()
+            },
+        ];
+        let mut json = Vec::new();
+        write(&mut json, &bookmarks).unwrap();
+        let json = serde_json::from_slice::<Value>(&json).unwrap();
+
+        // Computed separately from the ids, UTF-16LE titles, types and urls of the nodes in order.
+        assert_eq!(json["checksum"], "a32bf2efd7d6b0a2c987c33dcd06e23d");
+        let dev = &json["roots"]["bookmark_bar"]["children"][0];
+        assert_eq!(dev["id"], "4");
+        assert_eq!(dev["name"], "Dev");
+        assert_eq!(dev["children"][0]["id"], "5");
+        assert_eq!(json["roots"]["other"]["children"][0]["id"], "6");
+    }
+
+    #[test]
+    fn guids_are_version_4_uuids() {
+        assert_eq!(
+            guid("5", "https://a.example/"),
+            "3547f901-d3ca-493f-b1fb-e4d9d59e1909"
+        );
+        for (id, name) in [("1", ""), ("42", "Folder"), ("7", "https://b.example/")] {
+            let guid = guid(id, name);
+            let groups = guid.split('-').map(str::len).collect::<Vec<_>>();
+            assert_eq!(groups, [8, 4, 4, 4, 12], "{guid}");
+            assert!(guid.bytes().all(|b| b == b'-' || b.is_ascii_hexdigit()));
+            assert_eq!(&guid[14..15], "4", "{guid}");
+            assert!("89ab".contains(&guid[19..20]), "{guid}");
+        }
+    }
+}
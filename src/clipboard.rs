@@ -0,0 +1,57 @@
+use std::{
+    env,
+    io::{stdout, Write},
+    process::Stdio,
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+use crate::{command_line::CommandLine, Result};
+
+/// Where copied text is sent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Clipboard {
+    /// The OSC 52 escape sequence, handled by the terminal even over ssh.
+    Terminal,
+    /// A command reading the text from its standard input such as `wl-copy` or `xclip`.
+    Command(CommandLine),
+}
+
+impl Clipboard {
+    /// Clipboard using the given command line, or the terminal if there is none.
+    pub fn new(command: Option<&str>) -> Self {
+        command
+            .and_then(CommandLine::parse)
+            .map_or(Self::Terminal, Self::Command)
+    }
+
+    pub fn copy(&self, text: &str) -> Result<()> {
+        match self {
+            Self::Terminal => {
+                let mut sequence = format!("\x1b]52;c;{}\x07", STANDARD.encode(text));
+                // Tmux only passes sequences on to the terminal when wrapped.
+                if env::var_os("TMUX").is_some() {
+                    sequence = format!("\x1bPtmux;{}\x1b\\", sequence.replace('\x1b', "\x1b\x1b"));
+                }
+                let mut out = stdout();
+                out.write_all(sequence.as_bytes())?;
+                out.flush()?;
+                Ok(())
+            }
+            Self::Command(command) => {
+                let mut child = command
+                    .command()
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null())
+                    .spawn()
+                    .map_err(|source| command.open_error(source))?;
+
+                if let Some(mut stdin) = child.stdin.take() {
+                    stdin.write_all(text.as_bytes())?;
+                }
+                command.check_status(child.wait()?)
+            }
+        }
+    }
+}
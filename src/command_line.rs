@@ -0,0 +1,59 @@
+use std::{
+    io,
+    process::{Command, ExitStatus},
+};
+
+use crate::{Error, Result};
+
+/// Program and arguments of a command line given by the user, split on whitespace.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandLine {
+    pub program: String,
+    pub args: Vec<String>,
+}
+
+impl CommandLine {
+    /// Split a command line, lines without a program are rejected.
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut words = line.split_whitespace().map(String::from);
+        Some(Self {
+            program: words.next()?,
+            args: words.collect(),
+        })
+    }
+
+    /// Command line running a program without arguments.
+    pub fn program(program: &str) -> Self {
+        Self {
+            program: program.into(),
+            args: Vec::new(),
+        }
+    }
+
+    /// Command running the program with the arguments, more can be added to it.
+    pub fn command(&self) -> Command {
+        let mut command = Command::new(&self.program);
+        command.args(&self.args);
+        command
+    }
+
+    /// Error for the program failing to start.
+    pub fn open_error(&self, source: io::Error) -> Error {
+        Error::Open {
+            program: self.program.clone(),
+            source,
+        }
+    }
+
+    /// Fail if the program did not exit successfully.
+    pub fn check_status(&self, status: ExitStatus) -> Result<()> {
+        if status.success() {
+            Ok(())
+        } else {
+            Err(Error::Exited {
+                program: self.program.clone(),
+                status,
+            })
+        }
+    }
+}
@@ -0,0 +1,34 @@
+use std::{env, path::Path};
+
+use crate::{command_line::CommandLine, Result};
+
+/// Command used to edit files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Editor {
+    command: CommandLine,
+}
+
+impl Editor {
+    /// Editor given by `$VISUAL` or `$EDITOR` with `vi` as fallback.
+    pub fn from_env() -> Self {
+        let command = ["VISUAL", "EDITOR"]
+            .into_iter()
+            .filter_map(|var| env::var(var).ok())
+            .find_map(|line| CommandLine::parse(&line))
+            .unwrap_or_else(|| CommandLine::program("vi"));
+        Self { command }
+    }
+
+    /// Edit a file in the terminal, waiting for the editor to exit. The cursor is placed on the
+    /// given line using the "+line" argument most editors understand.
+    pub fn edit(&self, path: &Path, line: Option<usize>) -> Result<()> {
+        let status = self
+            .command
+            .command()
+            .args(line.map(|line| format!("+{}", line + 1)))
+            .arg(path)
+            .status()
+            .map_err(|source| self.command.open_error(source))?;
+        self.command.check_status(status)
+    }
+}
@@ -0,0 +1,52 @@
+use std::{fs, path::Path};
+
+use clap::ValueEnum;
+
+use crate::{bookmark::Bookmark, buku, chromium, netscape, save::write_atomic, Error, Result};
+
+/// Formats bookmarks can be exported to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// A bookmarks.html file browsers can import
+    Netscape,
+    /// The Bookmarks file in a Chromium, Chrome, Brave or Edge profile
+    Chromium,
+    /// The bookmarks.db database of buku, bookmarks are added to it
+    Buku,
+}
+
+impl Format {
+    /// Guess the format from the name of the file being written, the Netscape format by default.
+    pub fn from_path(path: &Path) -> Self {
+        let extension = path.extension().unwrap_or_default();
+        if extension == "json" || path.file_name().is_some_and(|name| name == "Bookmarks") {
+            Self::Chromium
+        } else if extension == "db" {
+            Self::Buku
+        } else {
+            Self::Netscape
+        }
+    }
+}
+
+/// Write the bookmarks to the file at `path` in the given format or the one guessed from the path.
+/// Files are replaced, except for buku databases which the bookmarks are added to. The bookmark
+/// file `source` the bookmarks come from is never written over.
+pub fn write(
+    format: Option<Format>,
+    path: &Path,
+    source: &Path,
+    bookmarks: &[Bookmark],
+) -> Result<()> {
+    let canonical = |path| fs::canonicalize(path).ok();
+    if canonical(path).is_some_and(|path| canonical(source) == Some(path)) {
+        return Err(Error::ExportOverSource(path.into()));
+    }
+    match format.unwrap_or_else(|| Format::from_path(path)) {
+        Format::Netscape => write_atomic(path, false, |writer| {
+            Ok(netscape::write(writer, bookmarks)?)
+        }),
+        Format::Chromium => write_atomic(path, false, |writer| chromium::write(writer, bookmarks)),
+        Format::Buku => buku::write(path, bookmarks),
+    }
+}
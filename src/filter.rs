@@ -0,0 +1,238 @@
+use std::ops::Range;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{bookmark::Entry, source::Source, Result};
+
+/// Score given to every matched grapheme cluster.
+const SCORE_MATCH: i64 = 16;
+/// Bonus for a match directly following the previous one.
+const BONUS_CONSECUTIVE: i64 = 8;
+/// Bonus for a match at the start of a word.
+const BONUS_BOUNDARY: i64 = 8;
+/// Penalty for starting a gap between matches.
+const PENALTY_GAP_START: i64 = 3;
+/// Penalty for every grapheme cluster a gap is extended by.
+const PENALTY_GAP_EXTENSION: i64 = 1;
+/// Largest penalty for the distance from the start of the text to the first match.
+const MAX_PENALTY_LEADING: i64 = 15;
+
+/// Fuzzy pattern, made up of whitespace separated terms which all have to match.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    text: String,
+    terms: Vec<Vec<String>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FuzzyMatch {
+    pub score: i64,
+    /// Sorted non overlapping byte ranges of the matched text.
+    pub ranges: Vec<Range<usize>>,
+}
+
+impl Pattern {
+    /// Create a pattern, patterns without any terms are rejected.
+    pub fn new(text: &str) -> Option<Self> {
+        let terms = text
+            .split_whitespace()
+            .map(|term| term.graphemes(true).map(str::to_lowercase).collect())
+            .collect::<Vec<_>>();
+
+        if terms.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            text: text.into(),
+            terms,
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    pub fn fuzzy_match(&self, haystack: &str) -> Option<FuzzyMatch> {
+        let graphemes = haystack
+            .grapheme_indices(true)
+            .map(|(i, g)| (i, g.to_lowercase()))
+            .collect::<Vec<_>>();
+
+        let mut score = 0;
+        let mut positions = Vec::new();
+        for term in &self.terms {
+            let (term_score, term_positions) = match_term(&graphemes, term)?;
+            score += term_score;
+            positions.extend(term_positions);
+        }
+
+        positions.sort_unstable();
+        positions.dedup();
+
+        let mut ranges = Vec::<Range<usize>>::new();
+        for pos in positions {
+            let start = graphemes[pos].0;
+            let end = graphemes.get(pos + 1).map_or(haystack.len(), |(i, _)| *i);
+            match ranges.last_mut() {
+                Some(last) if last.end == start => last.end = end,
+                _ => ranges.push(start..end),
+            }
+        }
+
+        Some(FuzzyMatch { score, ranges })
+    }
+}
+
+/// Match a single term against the graphemes of a text, giving the score and matched positions.
+///
+/// The first occurrence of the term as a subsequence is found scanning forwards, after which a
+/// backwards scan from its end tightens the match.
+fn match_term(graphemes: &[(usize, String)], term: &[String]) -> Option<(i64, Vec<usize>)> {
+    let mut pending = term.iter().peekable();
+    let end = graphemes.iter().position(|(_, g)| {
+        if pending.next_if(|t| *t == g).is_some() {
+            pending.peek().is_none()
+        } else {
+            false
+        }
+    })?;
+
+    let mut positions = vec![0; term.len()];
+    let mut pending = term.iter().enumerate().rev().peekable();
+    for (i, (_, g)) in graphemes[..=end].iter().enumerate().rev() {
+        if let Some((k, _)) = pending.next_if(|(_, t)| *t == g) {
+            positions[k] = i;
+        }
+        if pending.peek().is_none() {
+            break;
+        }
+    }
+
+    let is_boundary = |i: usize| i == 0 || graphemes[i - 1].1.chars().all(|c| !c.is_alphanumeric());
+
+    let mut score = -(positions[0] as i64).min(MAX_PENALTY_LEADING);
+    for (k, &pos) in positions.iter().enumerate() {
+        score += SCORE_MATCH;
+        if is_boundary(pos) {
+            score += BONUS_BOUNDARY;
+        }
+        if k > 0 {
+            let gap = (pos - positions[k - 1] - 1) as i64;
+            if gap == 0 {
+                score += BONUS_CONSECUTIVE;
+            } else {
+                score -= PENALTY_GAP_START + (gap - 1) * PENALTY_GAP_EXTENSION;
+            }
+        }
+    }
+
+    Some((score, positions))
+}
+
+/// Entry matched by a filter.
+#[derive(Debug, Clone)]
+pub struct Hit {
+    /// Line of the entry in the file.
+    pub line: usize,
+    pub entry: Entry,
+    score: i64,
+}
+
+/// Entries matching a pattern, best match first.
+#[derive(Debug, Clone)]
+pub struct Filter {
+    pattern: Pattern,
+    hits: Vec<Hit>,
+}
+
+impl Filter {
+    pub fn new(pattern: Pattern, source: &mut Source) -> Result<Self> {
+        let mut hits = Vec::new();
+        for (line, entry) in source.entries_from(0)?.enumerate() {
+            let entry = entry?;
+            if let Some(FuzzyMatch { score, .. }) = pattern.fuzzy_match(&entry.label()) {
+                hits.push(Hit { line, entry, score });
+            }
+        }
+
+        // Stable sort keeps matches with equal score in file order.
+        hits.sort_by_key(|hit| -hit.score);
+
+        Ok(Self { pattern, hits })
+    }
+
+    pub fn pattern(&self) -> &Pattern {
+        &self.pattern
+    }
+
+    pub fn hits(&self) -> &[Hit] {
+        &self.hits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    fn score(pattern: &str, text: &str) -> i64 {
+        Pattern::new(pattern)
+            .unwrap()
+            .fuzzy_match(text)
+            .unwrap()
+            .score
+    }
+
+    /// Matched byte ranges as pairs of start and end.
+    fn ranges(pattern: &str, text: &str) -> Vec<(usize, usize)> {
+        let fuzzy_match = Pattern::new(pattern).unwrap().fuzzy_match(text).unwrap();
+        fuzzy_match
+            .ranges
+            .into_iter()
+            .map(|range| (range.start, range.end))
+            .collect()
+    }
+
+    #[test]
+    fn consecutive_and_word_start_matches_rank_higher() {
+        assert!(score("rust", "trust") > score("rust", "rxuxsxt"));
+        assert!(score("ab", "a-b") > score("ab", "axb"));
+        assert!(score("fb", "foo bar") > score("fb", "foobar"));
+        // The backwards scan finds the tighter match after the first one.
+        assert_eq!(ranges("ab", "a-xx-ab"), [(5, 7)]);
+        assert_eq!(Pattern::new("xyz").unwrap().fuzzy_match("zyx"), None);
+        assert_eq!(Pattern::new(" \t"), None);
+    }
+
+    #[test]
+    fn ranges_are_merged_byte_ranges_of_graphemes() {
+        // Matches of different terms are merged when they touch.
+        assert_eq!(ranges("ab cd", "abcd"), [(0, 4)]);
+        assert_eq!(ranges("ün e", "Ünïcode"), [(0, 3), (8, 9)]);
+        // A combining mark belongs to the character before it.
+        assert_eq!(ranges("y", "x\u{301}y"), [(3, 4)]);
+        assert_eq!(ranges("x\u{301}", "x\u{301}y"), [(0, 3)]);
+        assert_eq!(Pattern::new("x").unwrap().fuzzy_match("x\u{301}"), None);
+    }
+
+    #[test]
+    fn hits_are_sorted_by_score_then_file_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bookmarks.txt");
+        fs::write(
+            &path,
+            "https://x.example\tr-u-s-t\n\
+             https://a.example\tRust\n\
+             https://b.example\tnothing\n\
+             https://c.example\tRust\n",
+        )
+        .unwrap();
+        let mut source = Source::open(&path).unwrap();
+
+        let filter = Filter::new(Pattern::new("rust").unwrap(), &mut source).unwrap();
+        let lines = filter.hits().iter().map(|hit| hit.line).collect::<Vec<_>>();
+        assert_eq!(lines, [1, 3, 0]);
+    }
+}
@@ -0,0 +1,298 @@
+use std::{
+    collections::HashMap,
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use rusqlite::{Connection, OpenFlags};
+use tempfile::TempDir;
+
+use crate::{bookmark::Bookmark, Result};
+
+/// Values of `moz_bookmarks.type`.
+const TYPE_BOOKMARK: i64 = 1;
+const TYPE_FOLDER: i64 = 2;
+
+/// Guid of the folder holding a folder for every tag.
+const TAGS_GUID: &str = "tags________";
+
+/// Read the bookmarks of a Firefox `places.sqlite` database, folders become categories.
+///
+/// The database is copied first as Firefox keeps it locked while running, the copy is only read.
+pub fn read(path: &Path) -> Result<Vec<Bookmark>> {
+    let snapshot = Snapshot::new(path)?;
+    let db = Connection::open_with_flags(
+        &snapshot.path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )?;
+
+    let mut statement = db.prepare(
+        "SELECT b.id, b.type, b.fk, b.parent, b.title, b.guid, b.dateAdded, b.lastModified,
+            p.url, p.description,
+            (SELECT keyword FROM moz_keywords WHERE place_id = b.fk LIMIT 1)
+        FROM moz_bookmarks b LEFT JOIN moz_places p ON p.id = b.fk
+        ORDER BY b.parent, b.position",
+    )?;
+    let items = statement
+        .query_map([], |row| {
+            Ok(Item {
+                id: row.get(0)?,
+                kind: row.get(1)?,
+                place: row.get(2)?,
+                parent: row.get(3)?,
+                title: row.get::<_, Option<String>>(4)?.unwrap_or_default(),
+                guid: row.get::<_, Option<String>>(5)?.unwrap_or_default(),
+                added: row.get(6)?,
+                modified: row.get(7)?,
+                url: row.get(8)?,
+                description: row.get::<_, Option<String>>(9)?.unwrap_or_default(),
+                keyword: row.get::<_, Option<String>>(10)?.unwrap_or_default(),
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let tree = Tree::new(&items);
+    let mut bookmarks = Vec::new();
+    for root in tree.children(0) {
+        tree.collect(root, &mut Vec::new(), &mut bookmarks);
+    }
+    Ok(bookmarks)
+}
+
+/// Row of `moz_bookmarks` along with the place it points to.
+#[derive(Debug)]
+struct Item {
+    id: i64,
+    kind: i64,
+    place: Option<i64>,
+    parent: i64,
+    title: String,
+    guid: String,
+    /// Microseconds since the unix epoch.
+    added: Option<i64>,
+    modified: Option<i64>,
+    url: Option<String>,
+    description: String,
+    keyword: String,
+}
+
+struct Tree<'a> {
+    /// Items by their parent, in order.
+    children: HashMap<i64, Vec<&'a Item>>,
+    /// Tags by place.
+    tags: HashMap<i64, Vec<String>>,
+}
+
+impl<'a> Tree<'a> {
+    fn new(items: &'a [Item]) -> Self {
+        let mut children = HashMap::<_, Vec<_>>::new();
+        for item in items {
+            children.entry(item.parent).or_default().push(item);
+        }
+
+        // Tagging a place adds a bookmark of it to the folder of the tag.
+        let mut tags = HashMap::<_, Vec<_>>::new();
+        let tag_folders = items.iter().filter(|item| item.guid == TAGS_GUID);
+        for tag in tag_folders.flat_map(|folder| children.get(&folder.id).into_iter().flatten()) {
+            for tagged in children.get(&tag.id).into_iter().flatten() {
+                if let Some(place) = tagged.place {
+                    tags.entry(place).or_default().push(tag.title.clone());
+                }
+            }
+        }
+
+        Self { children, tags }
+    }
+
+    fn children(&self, parent: i64) -> impl Iterator<Item = &'a Item> + '_ {
+        self.children.get(&parent).into_iter().flatten().copied()
+    }
+
+    /// Add the bookmarks in or below an item, `path` are the names of the folders it is in.
+    fn collect(&self, item: &Item, path: &mut Vec<String>, bookmarks: &mut Vec<Bookmark>) {
+        match item.kind {
+            TYPE_FOLDER => {
+                // Named as in the bookmarks.html files Firefox exports, where the menu is the top.
+                let name = match item.guid.as_str() {
+                    TAGS_GUID => return,
+                    "root________" | "menu________" => None,
+                    "toolbar_____" => Some("Bookmarks Toolbar"),
+                    "unfiled_____" => Some("Other Bookmarks"),
+                    "mobile______" => Some("Mobile Bookmarks"),
+                    _ => Some(item.title.as_str()),
+                };
+                path.extend(name.map(String::from));
+                for child in self.children(item.id) {
+                    self.collect(child, path, bookmarks);
+                }
+                if name.is_some() {
+                    path.pop();
+                }
+            }
+            TYPE_BOOKMARK => {
+                let Some(Ok(bookmark)) = item.url.as_deref().map(|url| Bookmark::new(url, "", ""))
+                else {
+                    return;
+                };
+                let seconds =
+                    |time: Option<i64>| time.and_then(|t| u64::try_from(t / 1_000_000).ok());
+                bookmarks.push(Bookmark {
+                    title: item.title.clone(),
+                    description: item.description.clone(),
+                    tags: item
+                        .place
+                        .and_then(|place| self.tags.get(&place))
+                        .cloned()
+                        .unwrap_or_default(),
+                    added: seconds(item.added),
+                    modified: seconds(item.modified),
+                    category: path.join("/"),
+                    keyword: item.keyword.clone(),
+                    ..bookmark
+                });
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Copy of a database and its write-ahead log in a private temporary directory, removed along
+/// with the directory when dropped.
+struct Snapshot {
+    path: PathBuf,
+    _dir: TempDir,
+}
+
+impl Snapshot {
+    fn new(path: &Path) -> Result<Self> {
+        let dir = tempfile::Builder::new().prefix("bookmark-tui-").tempdir()?;
+        let snapshot = Self {
+            path: dir.path().join("places.sqlite"),
+            _dir: dir,
+        };
+        fs::copy(path, &snapshot.path)?;
+        // Recent changes are only in the log until Firefox moves them into the database.
+        let log = with_suffix(path, "-wal");
+        if log.exists() {
+            fs::copy(log, with_suffix(&snapshot.path, "-wal"))?;
+        }
+        Ok(snapshot)
+    }
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = OsString::from(path);
+    name.push(suffix);
+    name.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCHEMA: &str = "
+        CREATE TABLE moz_places (id INTEGER PRIMARY KEY, url LONGVARCHAR, title LONGVARCHAR,
+            description TEXT);
+        CREATE TABLE moz_bookmarks (id INTEGER PRIMARY KEY, type INTEGER, fk INTEGER DEFAULT NULL,
+            parent INTEGER, position INTEGER, title LONGVARCHAR, dateAdded INTEGER,
+            lastModified INTEGER, guid TEXT);
+        CREATE TABLE moz_keywords (id INTEGER PRIMARY KEY AUTOINCREMENT, keyword TEXT UNIQUE,
+            place_id INTEGER);
+
+        INSERT INTO moz_places (id, url, title, description) VALUES
+            (1, 'https://www.mozilla.org/', 'Mozilla', 'Internet for people'),
+            (2, 'https://doc.rust-lang.org/', 'Docs', NULL),
+            (3, 'https://crates.io/', 'crates.io', NULL);
+
+        INSERT INTO moz_bookmarks (id, type, fk, parent, position, title, dateAdded, lastModified,
+            guid) VALUES
+            (1, 2, NULL, 0, 0, '', 0, 0, 'root________'),
+            (2, 2, NULL, 1, 0, 'menu', 0, 0, 'menu________'),
+            (3, 2, NULL, 1, 1, 'toolbar', 0, 0, 'toolbar_____'),
+            (4, 2, NULL, 1, 2, 'tags', 0, 0, 'tags________'),
+            (5, 2, NULL, 1, 3, 'unfiled', 0, 0, 'unfiled_____'),
+            (10, 1, 1, 2, 0, 'Mozilla', 1700000000000000, 1700000005000000, 'bookmark0001'),
+            (11, 2, NULL, 2, 1, 'Dev', 0, 0, 'folder000001'),
+            (12, 1, 2, 11, 0, 'Rust docs', 1700000000123456, NULL, 'bookmark0002'),
+            (20, 2, NULL, 4, 0, 'rust', 0, 0, 'tag000000001'),
+            (21, 1, 2, 20, 0, NULL, 0, 0, 'tag000000002'),
+            (22, 1, 3, 20, 1, NULL, 0, 0, 'tag000000003'),
+            (23, 2, NULL, 4, 1, 'docs', 0, 0, 'tag000000004'),
+            (24, 1, 2, 23, 0, NULL, 0, 0, 'tag000000005');
+
+        INSERT INTO moz_keywords (keyword, place_id) VALUES ('rs', 2);
+    ";
+
+    /// Changes made while Firefox runs, left in the write-ahead log.
+    const RECENT: &str = "
+        INSERT INTO moz_bookmarks (id, type, fk, parent, position, title, dateAdded, lastModified,
+            guid) VALUES (13, 1, 3, 3, 0, 'Crates', 1710000000000000, 1710000000000000,
+            'bookmark0003');
+        UPDATE moz_bookmarks SET title = 'Rust documentation' WHERE id = 12;
+    ";
+
+    #[test]
+    fn reads_bookmarks_with_uncheckpointed_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("places.sqlite");
+        let db = Connection::open(&path).unwrap();
+        db.execute_batch(SCHEMA).unwrap();
+        db.pragma_update(None, "journal_mode", "wal").unwrap();
+        db.pragma_update(None, "wal_autocheckpoint", 0).unwrap();
+        db.execute_batch(RECENT).unwrap();
+        // The connection stays open like Firefox keeps it, closing it would checkpoint the log.
+        assert!(fs::metadata(with_suffix(&path, "-wal")).unwrap().len() > 0);
+
+        let bookmarks = read(&path).unwrap();
+        drop(db);
+
+        let summary = bookmarks
+            .iter()
+            .map(|b| {
+                (
+                    b.url.as_str(),
+                    b.title.as_str(),
+                    b.category.as_str(),
+                    b.tags.join(","),
+                    b.keyword.as_str(),
+                    b.added,
+                    b.modified,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            [
+                (
+                    "https://www.mozilla.org/",
+                    "Mozilla",
+                    "",
+                    String::new(),
+                    "",
+                    Some(1_700_000_000),
+                    Some(1_700_000_005),
+                ),
+                (
+                    "https://doc.rust-lang.org/",
+                    "Rust documentation",
+                    "Dev",
+                    "rust,docs".into(),
+                    "rs",
+                    Some(1_700_000_000),
+                    None,
+                ),
+                (
+                    "https://crates.io/",
+                    "Crates",
+                    "Bookmarks Toolbar",
+                    "rust".into(),
+                    "",
+                    Some(1_710_000_000),
+                    Some(1_710_000_000),
+                ),
+            ]
+        );
+        assert_eq!(bookmarks[0].description, "Internet for people");
+    }
+}
@@ -0,0 +1,54 @@
+use crate::bookmark::Bookmark;
+
+/// Folder of bookmarks built from their categories, with its bookmarks and subfolders in the
+/// order they first appear.
+#[derive(Debug, Default)]
+pub struct Folder<'a> {
+    pub name: &'a str,
+    pub items: Vec<Item<'a>>,
+}
+
+#[derive(Debug)]
+pub enum Item<'a> {
+    Bookmark(&'a Bookmark),
+    Folder(Folder<'a>),
+}
+
+impl<'a> Folder<'a> {
+    /// Unnamed folder holding the folders of the categories of the bookmarks.
+    pub fn from_categories(bookmarks: &'a [Bookmark]) -> Self {
+        let mut root = Self::default();
+        for bookmark in bookmarks {
+            root.insert(category_path(&bookmark.category), bookmark);
+        }
+        root
+    }
+
+    /// Add a bookmark to the folder at `path` below this one, creating the folders missing.
+    pub fn insert(&mut self, mut path: impl Iterator<Item = &'a str>, bookmark: &'a Bookmark) {
+        let Some(name) = path.next() else {
+            self.items.push(Item::Bookmark(bookmark));
+            return;
+        };
+
+        let existing = self
+            .items
+            .iter()
+            .position(|item| matches!(item, Item::Folder(folder) if folder.name == name));
+        let index = existing.unwrap_or_else(|| {
+            self.items.push(Item::Folder(Folder {
+                name,
+                items: Vec::new(),
+            }));
+            self.items.len() - 1
+        });
+        if let Item::Folder(folder) = &mut self.items[index] {
+            folder.insert(path, bookmark);
+        }
+    }
+}
+
+/// Names of the folders of a category, outermost first.
+pub fn category_path(category: &str) -> impl Iterator<Item = &str> {
+    category.split('/').filter(move |_| !category.is_empty())
+}
@@ -0,0 +1,82 @@
+use crate::{
+    bookmark::{Bookmark, ParseError},
+    prompt::Prompt,
+};
+
+/// Labels of the fields of a form, in order.
+const LABELS: [&str; 3] = ["URL", "Title", "Tags"];
+
+/// Fields of a bookmark being entered.
+#[derive(Debug, Clone, Default)]
+pub struct Form {
+    fields: [Prompt; 3],
+    focus: usize,
+    /// Bookmark being edited, fields missing from the form are kept from it.
+    base: Bookmark,
+}
+
+impl Form {
+    /// Amount of rows taken up by a form.
+    pub const ROWS: usize = LABELS.len();
+
+    /// Form filled in with the fields of a bookmark.
+    pub fn edit(bookmark: Bookmark) -> Self {
+        Self {
+            fields: [
+                Prompt::new(&bookmark.url),
+                Prompt::new(&bookmark.title),
+                Prompt::new(&bookmark.tags.join(", ")),
+            ],
+            focus: 0,
+            base: bookmark,
+        }
+    }
+
+    /// Bookmark the form started with.
+    pub fn base(&self) -> &Bookmark {
+        &self.base
+    }
+
+    pub fn focused_mut(&mut self) -> &mut Prompt {
+        &mut self.fields[self.focus]
+    }
+
+    pub fn focus_next(&mut self) {
+        self.focus = (self.focus + 1) % self.fields.len();
+    }
+
+    pub fn focus_previous(&mut self) {
+        self.focus = (self.focus + self.fields.len() - 1) % self.fields.len();
+    }
+
+    /// Label, input and whether it has focus for every field.
+    pub fn fields(&self) -> impl Iterator<Item = (&'static str, &Prompt, bool)> {
+        LABELS
+            .into_iter()
+            .zip(&self.fields)
+            .enumerate()
+            .map(|(i, (label, prompt))| (label, prompt, i == self.focus))
+    }
+
+    /// Whether the input differs from the bookmark the form started with.
+    pub fn is_modified(&self) -> bool {
+        let initial = Self::edit(self.base.clone());
+        self.fields
+            .iter()
+            .zip(&initial.fields)
+            .any(|(field, initial)| field.text() != initial.text())
+    }
+
+    pub fn bookmark(&self) -> Result<Bookmark, ParseError> {
+        let [url, title, tags] = &self.fields;
+        let Bookmark {
+            url, title, tags, ..
+        } = Bookmark::new(url.text().trim(), title.text().trim(), tags.text())?;
+        Ok(Bookmark {
+            url,
+            title,
+            tags,
+            ..self.base.clone()
+        })
+    }
+}
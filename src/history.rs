@@ -0,0 +1,215 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::PathBuf,
+};
+
+use crate::{save::write_atomic, Error, Result};
+
+/// Most changes kept in the history.
+const HISTORY_LIMIT: usize = 1000;
+
+/// Replacement of lines starting at `line`, enough to both apply and revert an edit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    pub line: usize,
+    pub old: Vec<String>,
+    pub new: Vec<String>,
+}
+
+impl Change {
+    /// Change reverting this one.
+    pub fn inverse(&self) -> Self {
+        Self {
+            line: self.line,
+            old: self.new.clone(),
+            new: self.old.clone(),
+        }
+    }
+}
+
+/// Undo and redo stacks of changes, optionally kept in a file.
+#[derive(Debug, Default)]
+pub struct History {
+    undo: Vec<Change>,
+    redo: Vec<Change>,
+    path: Option<PathBuf>,
+}
+
+impl History {
+    /// History kept in the given file, read from it if it exists.
+    pub fn open(path: PathBuf) -> Result<Self> {
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut history = Self {
+            path: Some(path),
+            ..Self::default()
+        };
+        if !history.parse(&text) {
+            let path = history.path.take().unwrap_or_default();
+            return Err(Error::InvalidHistory(path));
+        }
+        Ok(history)
+    }
+
+    /// Push a change that was just made, dropping the changes that were undone.
+    pub fn record(&mut self, change: Change) -> Result<()> {
+        self.undo.push(change);
+        if self.undo.len() > HISTORY_LIMIT {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+        self.save()
+    }
+
+    pub fn last_undo(&self) -> Option<&Change> {
+        self.undo.last()
+    }
+
+    pub fn last_redo(&self) -> Option<&Change> {
+        self.redo.last()
+    }
+
+    /// Move the last change to the redo stack once it was reverted.
+    pub fn undone(&mut self) -> Result<()> {
+        if let Some(change) = self.undo.pop() {
+            self.redo.push(change);
+        }
+        self.save()
+    }
+
+    /// Move the last undone change back to the undo stack once it was applied again.
+    pub fn redone(&mut self) -> Result<()> {
+        if let Some(change) = self.redo.pop() {
+            self.undo.push(change);
+        }
+        self.save()
+    }
+
+    /// Write the history to its file, each change is a header line
+    /// "<stack> <line> <old count> <new count>" followed by the old and new lines.
+    fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        write_atomic(path, false, |writer| {
+            let stacks = [("undo", &self.undo), ("redo", &self.redo)];
+            for (stack, changes) in stacks {
+                for change in changes {
+                    writeln!(
+                        writer,
+                        "{stack} {} {} {}",
+                        change.line,
+                        change.old.len(),
+                        change.new.len()
+                    )?;
+                    for line in change.old.iter().chain(&change.new) {
+                        writeln!(writer, "{line}")?;
+                    }
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Read changes written by [History::save], returns false if the text is not valid.
+    fn parse(&mut self, text: &str) -> bool {
+        // Lines are split on line feeds only as bookmark lines may end with a carriage return.
+        let mut lines = text.split_terminator('\n');
+        while let Some(header) = lines.next() {
+            let mut fields = header.split(' ');
+            let stack = match fields.next() {
+                Some("undo") => &mut self.undo,
+                Some("redo") => &mut self.redo,
+                _ => return false,
+            };
+            let mut numbers = fields.map(str::parse::<usize>);
+            let (Some(Ok(line)), Some(Ok(old)), Some(Ok(new)), None) = (
+                numbers.next(),
+                numbers.next(),
+                numbers.next(),
+                numbers.next(),
+            ) else {
+                return false;
+            };
+
+            let old_lines = lines
+                .by_ref()
+                .take(old)
+                .map(String::from)
+                .collect::<Vec<_>>();
+            let new_lines = lines
+                .by_ref()
+                .take(new)
+                .map(String::from)
+                .collect::<Vec<_>>();
+            if old_lines.len() != old || new_lines.len() != new {
+                return false;
+            }
+            stack.push(Change {
+                line,
+                old: old_lines,
+                new: new_lines,
+            });
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn change(line: usize, old: &[&str], new: &[&str]) -> Change {
+        Change {
+            line,
+            old: old.iter().map(|&line| line.into()).collect(),
+            new: new.iter().map(|&line| line.into()).collect(),
+        }
+    }
+
+    #[test]
+    fn history_is_saved_and_read_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bookmarks.txt.undo");
+
+        let mut history = History::open(path.clone()).unwrap();
+        history
+            .record(change(0, &[], &["https://a", "https://b\r"]))
+            .unwrap();
+        history
+            .record(change(1, &["https://b\r"], &["", "# undo 1 2 3"]))
+            .unwrap();
+        history.record(change(3, &["https://c"], &[])).unwrap();
+        history.undone().unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "undo 0 0 2\nhttps://a\nhttps://b\r\n\
+             undo 1 1 2\nhttps://b\r\n\n# undo 1 2 3\n\
+             redo 3 1 0\nhttps://c\n"
+        );
+
+        let read = History::open(path).unwrap();
+        assert_eq!(read.undo, history.undo);
+        assert_eq!(read.redo, history.redo);
+    }
+
+    #[test]
+    fn invalid_history_is_rejected() {
+        for text in [
+            "undo 0 1\nline\n",
+            "undo 0 1 1 1\nold\nnew\n",
+            "todo 0 0 0\n",
+            "undo 0 2 0\nonly one\n",
+            "redo x 0 0\n",
+        ] {
+            assert!(!History::default().parse(text), "{text:?}");
+        }
+        assert!(History::default().parse(""));
+    }
+}
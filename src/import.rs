@@ -0,0 +1,84 @@
+use std::{
+    fs::{self, File},
+    io::{self, Read, Write},
+    path::Path,
+};
+
+use clap::ValueEnum;
+
+use crate::{bookmark::Bookmark, buku, chromium, firefox, netscape, save::write_atomic, Result};
+
+/// Start of every SQLite database.
+const SQLITE_HEADER: &[u8] = b"SQLite format 3\0";
+
+/// Formats bookmarks can be imported from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// The bookmarks.html file browsers export
+    Netscape,
+    /// The places.sqlite database in a Firefox profile
+    Firefox,
+    /// The Bookmarks file in a Chromium, Chrome, Brave or Edge profile
+    Chromium,
+    /// The bookmarks.db database of buku
+    Buku,
+}
+
+impl Format {
+    /// Guess the format of a file from its first bytes, SQLite databases ending in ".db" are taken
+    /// to be buku databases.
+    pub fn detect(path: &Path) -> Result<Self> {
+        let mut start = Vec::new();
+        File::open(path)?
+            .take(SQLITE_HEADER.len() as u64)
+            .read_to_end(&mut start)?;
+        Ok(if start == SQLITE_HEADER {
+            if path.extension().is_some_and(|extension| extension == "db") {
+                Self::Buku
+            } else {
+                Self::Firefox
+            }
+        } else if start.trim_ascii_start().starts_with(b"{") {
+            Self::Chromium
+        } else {
+            Self::Netscape
+        })
+    }
+}
+
+/// Read the bookmarks of a file, in the given format or the detected one.
+pub fn read(format: Option<Format>, path: &Path) -> Result<Vec<Bookmark>> {
+    let format = match format {
+        Some(format) => format,
+        None => Format::detect(path)?,
+    };
+    match format {
+        Format::Netscape => {
+            let html = fs::read(path)?;
+            Ok(netscape::parse(&String::from_utf8_lossy(&html)))
+        }
+        Format::Firefox => firefox::read(path),
+        Format::Chromium => chromium::read(path),
+        Format::Buku => buku::read(path),
+    }
+}
+
+/// Add bookmarks to the end of a bookmark file, creating it if it does not exist.
+pub fn append(path: &Path, bookmarks: &[Bookmark]) -> Result<()> {
+    let existing = match fs::read(path) {
+        Ok(existing) => existing,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Vec::new(),
+        Err(err) => return Err(err.into()),
+    };
+
+    write_atomic(path, false, |writer| {
+        writer.write_all(&existing)?;
+        if existing.last().is_some_and(|&last| last != b'\n') {
+            writeln!(writer)?;
+        }
+        for bookmark in bookmarks {
+            writeln!(writer, "{bookmark}")?;
+        }
+        Ok(())
+    })
+}
@@ -0,0 +1,303 @@
+use std::fmt::{self, Display};
+
+use clap::ValueEnum;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{
+    bookmark::Entry,
+    width::{fit_width, grapheme_width},
+};
+
+const ELLIPSIS: &str = "…";
+
+/// Horizontal placement of lines narrower than the terminal.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Align {
+    Left,
+    #[default]
+    Center,
+    Right,
+}
+
+/// How lines wider than the terminal are shortened.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Truncate {
+    /// Cut the end of the line, marked by an ellipsis.
+    #[default]
+    End,
+    /// Cut the middle of the line, keeping the domain and last path segment of urls.
+    Middle,
+    /// Continue the line on the following rows.
+    Wrap,
+}
+
+impl Align {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Left => Self::Center,
+            Self::Center => Self::Right,
+            Self::Right => Self::Left,
+        }
+    }
+
+    /// Cells left of a line of the given width.
+    pub fn offset(self, width: usize, max_width: usize) -> usize {
+        let diff = max_width.saturating_sub(width);
+        match self {
+            Self::Left => 0,
+            Self::Center => diff / 2,
+            Self::Right => diff,
+        }
+    }
+}
+
+impl Truncate {
+    pub fn next(self) -> Self {
+        match self {
+            Self::End => Self::Middle,
+            Self::Middle => Self::Wrap,
+            Self::Wrap => Self::End,
+        }
+    }
+}
+
+impl Display for Align {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Left => "left",
+            Self::Center => "center",
+            Self::Right => "right",
+        })
+    }
+}
+
+impl Display for Truncate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::End => "end",
+            Self::Middle => "middle",
+            Self::Wrap => "wrap",
+        })
+    }
+}
+
+/// Rows an entry is shown as, each fitting in the given width. The first `offset` grapheme
+/// clusters are scrolled out of view.
+pub fn entry_rows(
+    entry: &Entry,
+    truncate: Truncate,
+    max_width: usize,
+    offset: usize,
+) -> Vec<String> {
+    // Control characters would move the terminal cursor.
+    let label = entry.label().replace(char::is_control, " ");
+
+    if offset != 0 {
+        return scrolled_rows(&label, truncate, max_width, offset);
+    }
+
+    if str_width(&label) <= max_width {
+        return vec![label];
+    }
+
+    match truncate {
+        Truncate::End => vec![ellipsize_end(&label, max_width)],
+        Truncate::Middle => vec![match entry {
+            Entry::Bookmark(bookmark) => {
+                let url = shorten_url(&bookmark.url).replace(char::is_control, " ");
+                let title = bookmark.title.replace(char::is_control, " ");
+                ellipsize_bookmark(&title, &url, max_width)
+            }
+            _ => ellipsize_middle(&label, max_width),
+        }],
+        Truncate::Wrap => wrap(&label, max_width),
+    }
+}
+
+/// Rows of text scrolled horizontally, with the hidden start marked by an ellipsis.
+fn scrolled_rows(label: &str, truncate: Truncate, max_width: usize, offset: usize) -> Vec<String> {
+    if max_width == 0 {
+        return vec![String::new()];
+    }
+
+    let start = label
+        .grapheme_indices(true)
+        .nth(offset)
+        .map_or(label.len(), |(i, _)| i);
+    let text = &label[start..];
+    let max_width = max_width.saturating_sub(1);
+
+    let mut rows = if str_width(text) <= max_width {
+        vec![text.to_owned()]
+    } else if truncate == Truncate::Wrap {
+        wrap(text, max_width)
+    } else {
+        vec![ellipsize_end(text, max_width)]
+    };
+    rows[0].insert_str(0, ELLIPSIS);
+    rows
+}
+
+/// Amount of grapheme clusters that have to be scrolled out of view for the rest of the text to
+/// fit in the given width after the ellipsis marking them.
+pub fn overflow(text: &str, max_width: usize) -> usize {
+    let mut width = str_width(text);
+    if width <= max_width {
+        return 0;
+    }
+
+    let max_width = max_width.saturating_sub(1);
+    let mut count = 0;
+    for grapheme in text.graphemes(true) {
+        if width <= max_width {
+            break;
+        }
+        width -= grapheme_width(grapheme);
+        count += 1;
+    }
+    count
+}
+
+/// Terminal cells taken up by a string.
+pub fn str_width(text: &str) -> usize {
+    text.graphemes(true).map(grapheme_width).sum()
+}
+
+fn ellipsize_end(text: &str, max_width: usize) -> String {
+    if max_width == 0 {
+        return String::new();
+    }
+    format!("{}{ELLIPSIS}", &text[..fit_width(text, max_width - 1)])
+}
+
+fn ellipsize_middle(text: &str, max_width: usize) -> String {
+    if max_width == 0 {
+        return String::new();
+    }
+
+    let head_width = (max_width - 1) / 2;
+    let tail_width = max_width - 1 - head_width;
+
+    let head = &text[..fit_width(text, head_width)];
+
+    let mut tail_start = text.len();
+    let mut used = 0;
+    for (i, grapheme) in text.grapheme_indices(true).rev() {
+        used += grapheme_width(grapheme);
+        if used > tail_width {
+            break;
+        }
+        tail_start = i;
+    }
+
+    format!("{head}{ELLIPSIS}{}", &text[tail_start..])
+}
+
+/// Shorten a bookmark label, the title is cut before the url is.
+fn ellipsize_bookmark(title: &str, url: &str, max_width: usize) -> String {
+    if title.is_empty() {
+        return fit_middle(url, max_width);
+    }
+
+    let url = format!(" <{url}>");
+    let url_width = str_width(&url);
+    let label = format!("{title}{url}");
+    if str_width(&label) <= max_width {
+        return label;
+    }
+
+    // Keep at least a character and an ellipsis of the title.
+    if url_width + 2 <= max_width {
+        format!("{}{url}", ellipsize_end(title, max_width - url_width))
+    } else {
+        ellipsize_middle(&label, max_width)
+    }
+}
+
+fn fit_middle(text: &str, max_width: usize) -> String {
+    if str_width(text) <= max_width {
+        text.into()
+    } else {
+        ellipsize_middle(text, max_width)
+    }
+}
+
+/// Replace all but the last path segment of a url with an ellipsis.
+fn shorten_url(url: &str) -> String {
+    let Some((scheme, rest)) = url.split_once("://") else {
+        return url.into();
+    };
+    let Some((host, path)) = rest.split_once('/') else {
+        return url.into();
+    };
+
+    let segments = path
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>();
+    match segments.as_slice() {
+        [_, .., last] => format!("{scheme}://{host}/{ELLIPSIS}/{last}"),
+        _ => url.into(),
+    }
+}
+
+/// Split text into rows of the given width, grapheme clusters wider than a row are left out.
+fn wrap(text: &str, max_width: usize) -> Vec<String> {
+    let mut rows = Vec::new();
+    let mut row = String::new();
+    let mut used = 0;
+    for grapheme in text.graphemes(true) {
+        let width = grapheme_width(grapheme);
+        if width > max_width {
+            continue;
+        }
+        if used + width > max_width && !row.is_empty() {
+            rows.push(std::mem::take(&mut row));
+            used = 0;
+        }
+        row.push_str(grapheme);
+        used += width;
+    }
+    rows.push(row);
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rows_fit_narrow_widths() {
+        let entries = [
+            Entry::parse("https://example.com/a/b/c\t漢字 title"),
+            Entry::parse("https://example.com"),
+            Entry::parse("# 漢"),
+            Entry::parse("x"),
+        ];
+        let truncates = [Truncate::End, Truncate::Middle, Truncate::Wrap];
+        for entry in &entries {
+            for truncate in truncates {
+                for max_width in 0..6 {
+                    for offset in 0..4 {
+                        for row in entry_rows(entry, truncate, max_width, offset) {
+                            assert!(
+                                str_width(&row) <= max_width,
+                                "{row:?} for {entry:?} {truncate} {max_width} {offset}"
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn wide_graphemes_are_left_out_of_narrow_rows() {
+        assert_eq!(wrap("a漢b", 1), ["a", "b"]);
+        assert_eq!(wrap("a漢b", 2), ["a", "漢", "b"]);
+        assert_eq!(wrap("漢", 0), [""]);
+        assert_eq!(scrolled_rows("abc", Truncate::End, 0, 1), [""]);
+        assert_eq!(scrolled_rows("abc", Truncate::End, 1, 1), [ELLIPSIS]);
+        assert_eq!(scrolled_rows("abc", Truncate::End, 3, 1), ["…bc"]);
+    }
+}
@@ -1,210 +1,311 @@
 use std::{
-    fs::File,
-    io::{self, stdout, BufRead, BufReader, Seek, SeekFrom, Write},
-    iter::{self, FusedIterator},
+    io::{self, stdout, Write},
     path::PathBuf,
+    process::ExitStatus,
     result,
+    time::{Duration, Instant},
 };
 
-use clap::Parser;
+use app::{App, Config, Flow};
+use bookmark::{Entry, ParseError};
+use clap::{Parser, Subcommand};
+use clipboard::Clipboard;
 use crossterm::{
-    cursor::{Hide, MoveRight, MoveTo, Show},
-    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
-    style::Print,
+    cursor::{Hide, Show},
+    event::{self, DisableMouseCapture, EnableMouseCapture, Event},
     terminal::{
         self, Clear, ClearType, DisableLineWrap, EnableLineWrap, EnterAlternateScreen,
         LeaveAlternateScreen,
     },
     QueueableCommand,
 };
-use tap::{Pipe, Tap};
+use editor::Editor;
+use history::History;
+use layout::{Align, Truncate};
+use opener::Opener;
+use source::Source;
 use thiserror::Error;
-use unicode_segmentation::UnicodeSegmentation;
+
+mod app;
+mod bookmark;
+mod buku;
+mod chromium;
+mod clipboard;
+mod command_line;
+mod editor;
+mod export;
+mod filter;
+mod firefox;
+mod folder;
+mod form;
+mod history;
+mod import;
+mod layout;
+mod netscape;
+mod opener;
+mod prompt;
+mod render;
+mod save;
+mod screen;
+mod search;
+mod source;
+mod width;
 
 #[derive(Error, Debug)]
 enum Error {
     #[error(transparent)]
     Io(#[from] io::Error),
+    #[error("could not run \"{program}\": {source}")]
+    Open { program: String, source: io::Error },
+    #[error("selected line is not a bookmark")]
+    NotABookmark,
+    #[error("pattern not found: {0}")]
+    NoMatch(String),
+    #[error("not a line number: {0}")]
+    InvalidLine(String),
+    #[error("invalid bookmark: {0}")]
+    InvalidBookmark(#[from] ParseError),
+    #[error("lines cannot be moved while filtering")]
+    MoveFiltered,
+    #[error("the file was changed since, not undoing")]
+    HistoryConflict,
+    #[error("the bookmark being edited was changed on disk")]
+    EditConflict,
+    #[error("\"{program}\" exited with {status}")]
+    Exited { program: String, status: ExitStatus },
+    #[error("invalid undo history in {}", .0.display())]
+    InvalidHistory(PathBuf),
+    #[error("line {0} is past the end of the file")]
+    PastEnd(usize),
+    #[error("database error: {0}")]
+    Database(#[from] rusqlite::Error),
+    #[error("invalid json: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("not a Chromium bookmark file")]
+    NotChromium,
+    #[error("{} is the bookmark file, not exporting over it", .0.display())]
+    ExportOverSource(PathBuf),
 }
 
 type Result<T> = result::Result<T, Error>;
 
 #[derive(Debug, Parser)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Cli {
-    input: PathBuf,
-}
-
-fn display_centered(
-    mut writer: impl Write,
-    lines: impl IntoIterator<Item = Result<String>>,
-    (term_width, term_height): (u16, u16),
-) -> Result<()> {
-    writer.queue(Clear(ClearType::All))?;
-
-    for (row, line) in lines
-        .into_iter()
-        .chain(iter::once_with(String::new).map(Ok))
-        .take(term_height as usize)
-        .enumerate()
-    {
-        queue_centered_line(&mut writer, &line?, row as u16, term_width as usize)?;
-    }
-
-    Ok(())
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Bookmark file to view
+    #[arg(required = true)]
+    input: Option<PathBuf>,
+    /// Command used to open bookmarks, "%s" is replaced by the url [default: $BROWSER or xdg-open]
+    #[arg(long)]
+    opener: Option<String>,
+    /// Lines the view may be scrolled past the last page
+    #[arg(long, default_value_t = 0)]
+    overscroll: usize,
+    /// Placement of lines narrower than the terminal
+    #[arg(long, value_enum, default_value_t)]
+    align: Align,
+    /// How lines wider than the terminal are shortened
+    #[arg(long, value_enum, default_value_t)]
+    truncate: Truncate,
+    /// Scroll the selected line back and forth when it is too wide
+    #[arg(long)]
+    marquee: bool,
+    /// Keep the previous version of the file as <INPUT>.bak when saving
+    #[arg(long)]
+    backup: bool,
+    /// Keep the undo history in <INPUT>.undo so that it is kept after quitting
+    #[arg(long)]
+    undo_file: bool,
+    /// Command copied text is piped to, such as "wl-copy", "xclip -selection clipboard" or
+    /// "xsel -ib" [default: the terminal, using OSC 52]
+    #[arg(long)]
+    copy_command: Option<String>,
 }
 
-fn queue_centered_line(
-    mut writer: impl Write,
-    line: &str,
-    row: u16,
-    max_width: usize,
-) -> Result<()> {
-    writer
-        .queue(MoveTo(0, row))?
-        .queue(Clear(ClearType::CurrentLine))?;
-
-    // Setting capacity to string length should guarantee only 1 allocation since there should not
-    // be able to be more grapheme clusters than bytes.
-    let segment_buffer = Vec::with_capacity(line.len())
-        .tap_mut(|v| v.extend(line.grapheme_indices(true).map(|(i, _)| i)));
-
-    let width = segment_buffer.len();
-    let diff = max_width.max(width) - max_width.min(width);
-
-    // Text gets either padded or cut depending on length.
-    if width < max_width {
-        writer
-            .queue(MoveRight(diff as u16 / 2))?
-            .queue(Print(line))?;
-    } else {
-        writer.queue(Print(&line[segment_buffer[diff / 2]..]))?;
-    }
-
-    writer.flush()?;
-
-    Ok(())
-}
-
-enum RefLineIter<'a, R: ?Sized> {
-    Dead,
-    Alive(&'a mut R),
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Add the bookmarks of a browser to the end of a bookmark file
+    Import {
+        /// Format of the file to import [default: detected from its content]
+        #[arg(long, value_enum)]
+        format: Option<import::Format>,
+        /// File to import
+        source: PathBuf,
+        /// Bookmark file to add the bookmarks to, created if it does not exist [default: print
+        /// the bookmarks]
+        output: Option<PathBuf>,
+    },
+    /// Write the bookmarks of a bookmark file in a format browsers can import
+    Export {
+        /// Format of the file to write [default: Chromium for "Bookmarks" or *.json, buku for *.db,
+        /// Netscape otherwise]
+        #[arg(long, value_enum)]
+        format: Option<export::Format>,
+        /// Bookmark file to export
+        input: PathBuf,
+        /// File to write, replaced if it exists unless it is a buku database
+        output: PathBuf,
+    },
 }
 
-impl<'a, R> Iterator for RefLineIter<'a, R>
-where
-    R: BufRead + ?Sized,
-{
-    type Item = Result<String>;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        if let Self::Alive(reader) = self {
-            let mut buf = String::new();
-            match reader.read_line(&mut buf) {
-                Err(e) => Some(Err(Error::from(e))),
-                Ok(0) => {
-                    *self = Self::Dead;
-                    None
+fn run_command(command: Command) -> Result<()> {
+    match command {
+        Command::Import {
+            format,
+            source,
+            output,
+        } => {
+            let bookmarks = import::read(format, &source)?;
+            match output {
+                Some(output) => {
+                    import::append(&output, &bookmarks)?;
+                    println!("imported {} bookmarks", bookmarks.len());
+                }
+                None => {
+                    for bookmark in &bookmarks {
+                        println!("{bookmark}");
+                    }
                 }
-                Ok(_) => Some(Ok(buf)),
             }
-        } else {
-            None
+        }
+        Command::Export {
+            format,
+            input,
+            output,
+        } => {
+            let mut bookmarks = Vec::new();
+            for entry in Source::open(&input)?.entries_from(0)? {
+                if let Entry::Bookmark(bookmark) = entry? {
+                    bookmarks.push(bookmark);
+                }
+            }
+            export::write(format, &output, &input, &bookmarks)?;
+            println!("exported {} bookmarks", bookmarks.len());
         }
     }
+    Ok(())
 }
 
-impl<'a, R> FusedIterator for RefLineIter<'a, R> where R: BufRead + ?Sized {}
-
-trait BufReadRefLineExt: BufRead {
-    fn ref_lines(&mut self) -> RefLineIter<'_, Self> {
-        RefLineIter::Alive(self)
-    }
-}
-
-impl<T: BufRead> BufReadRefLineExt for T {}
+/// How long to wait for input before handling background work.
+const TICK: Duration = Duration::from_millis(100);
 
 struct TermGuard;
 
 impl TermGuard {
     fn new() -> Result<Self> {
-        terminal::enable_raw_mode()?;
-        stdout()
-            .queue(EnterAlternateScreen)?
-            .queue(Clear(ClearType::All))?
-            .queue(Hide)?
-            .queue(DisableLineWrap)?
-            .flush()?;
+        enter_tui()?;
         Ok(Self)
     }
 }
 
 impl Drop for TermGuard {
     fn drop(&mut self) {
-        let mut out = stdout();
+        leave_tui();
+    }
+}
 
-        let _ = out.queue(Clear(ClearType::All));
-        let _ = out.queue(LeaveAlternateScreen);
-        let _ = out.queue(Show);
-        let _ = out.queue(EnableLineWrap);
-        let _ = out.flush();
+/// Set up the terminal for the tui.
+fn enter_tui() -> Result<()> {
+    terminal::enable_raw_mode()?;
+    stdout()
+        .queue(EnterAlternateScreen)?
+        .queue(Clear(ClearType::All))?
+        .queue(Hide)?
+        .queue(DisableLineWrap)?
+        .queue(EnableMouseCapture)?
+        .flush()?;
+    Ok(())
+}
 
-        let _ = terminal::disable_raw_mode();
-    }
+/// Give the terminal back in the state it was in before [enter_tui].
+fn leave_tui() {
+    let mut out = stdout();
+
+    let _ = out.queue(DisableMouseCapture);
+    let _ = out.queue(Clear(ClearType::All));
+    let _ = out.queue(LeaveAlternateScreen);
+    let _ = out.queue(Show);
+    let _ = out.queue(EnableLineWrap);
+    let _ = out.flush();
+
+    let _ = terminal::disable_raw_mode();
 }
 
 fn main() -> Result<()> {
-    let Cli { input } = Cli::parse();
+    let Cli {
+        command,
+        input,
+        opener,
+        overscroll,
+        align,
+        truncate,
+        marquee,
+        backup,
+        undo_file,
+        copy_command,
+    } = Cli::parse();
+    if let Some(command) = command {
+        return run_command(command);
+    }
+    let input = input.expect("the input is required without a subcommand");
     let _guard = TermGuard::new();
 
     let mut writer = stdout();
 
-    let mut file = File::open(&input)?.pipe(BufReader::new);
-    let start_pos = file.stream_position()?;
-
-    let mut display = |scroll_pos, size| -> Result<()> {
-        file.seek(SeekFrom::Start(start_pos))?;
-        display_centered(&mut writer, file.ref_lines().skip(scroll_pos), size)?;
-        Ok(())
+    let source = Source::open(&input)?;
+    let history = if undo_file {
+        let mut path = input.into_os_string();
+        path.push(".undo");
+        History::open(path.into())?
+    } else {
+        History::default()
     };
+    let opener = opener
+        .as_deref()
+        .and_then(Opener::new)
+        .unwrap_or_else(Opener::from_env);
 
-    let mut scroll_pos = 0usize;
-    let mut size = terminal::size()?;
+    let config = Config {
+        opener,
+        overscroll,
+        align,
+        truncate,
+        marquee,
+        backup,
+        editor: Editor::from_env(),
+        clipboard: Clipboard::new(copy_command.as_deref()),
+    };
+    let mut app = App::new(source, history, config, terminal::size()?);
 
-    display(scroll_pos, size)?;
+    app.display(&mut writer)?;
+    let mut last_tick = Instant::now();
     'event_l: loop {
+        // Background work is handled every tick even while input keeps arriving.
+        if last_tick.elapsed() >= TICK {
+            last_tick = Instant::now();
+            if app.tick()? {
+                app.display(&mut writer)?
+            }
+        }
+        if !event::poll(TICK.saturating_sub(last_tick.elapsed()))? {
+            continue;
+        }
+
         match event::read()? {
-            Event::Key(key_event) => match key_event {
-                KeyEvent {
-                    code: KeyCode::Char('c'),
-                    modifiers: KeyModifiers::CONTROL,
-                    ..
-                }
-                | KeyEvent {
-                    code: KeyCode::Char('q'),
-                    ..
-                } => break 'event_l,
-                KeyEvent {
-                    kind: KeyEventKind::Press,
-                    code,
-                    ..
-                } => match code {
-                    KeyCode::Down => {
-                        scroll_pos = scroll_pos.saturating_add(1);
-                        display(scroll_pos, size)?
-                    }
-                    KeyCode::Up => {
-                        scroll_pos = scroll_pos.saturating_sub(1);
-                        display(scroll_pos, size)?
-                    }
-                    _ => (),
-                },
-                _ => (),
-            },
-            Event::Resize(w, h) => {
-                if (w, h) != size {
-                    size = (w, h);
-                    display(scroll_pos, size)?
+            Event::Key(key_event) => {
+                if app.handle_key(key_event)? == Flow::Quit {
+                    break 'event_l;
                 }
+                app.display(&mut writer)?
+            }
+            Event::Mouse(mouse_event) if app.handle_mouse(mouse_event)? => {
+                app.display(&mut writer)?
+            }
+            Event::Resize(w, h) if (w, h) != app.size() => {
+                app.resize((w, h));
+                app.display(&mut writer)?
             }
             _ => (),
         }
@@ -0,0 +1,472 @@
+use std::io::{self, Write};
+
+use crate::{
+    bookmark::Bookmark,
+    folder::{Folder, Item},
+};
+
+/// Start of a bookmark file, as written by browsers.
+const HEADER: &str = "<!DOCTYPE NETSCAPE-Bookmark-file-1>
+<!-- This is an automatically generated file.
+     It will be read and overwritten.
+     DO NOT EDIT! -->
+<META HTTP-EQUIV=\"Content-Type\" CONTENT=\"text/html; charset=UTF-8\">
+<TITLE>Bookmarks</TITLE>
+<H1>Bookmarks</H1>
+";
+
+/// Bookmarks of a Netscape bookmark file as exported by browsers, folders become categories.
+///
+/// Exporters differ in the details so parsing is lenient, anchors without a valid url are
+/// skipped.
+pub fn parse(html: &str) -> Vec<Bookmark> {
+    let mut parser = Parser::default();
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        parser.text(&rest[..start]);
+        rest = &rest[start..];
+
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+        } else if let Some((tag, after)) = Tag::parse(rest) {
+            parser.tag(tag);
+            rest = after;
+        } else {
+            parser.text("<");
+            rest = &rest[1..];
+        }
+    }
+    parser.text(rest);
+    parser.finish_text();
+    parser.bookmarks
+}
+
+/// Write bookmarks as a Netscape bookmark file, categories become folders.
+///
+/// Bookmarks are grouped by category with each folder placed where its first bookmark is. Parsing
+/// the file gives back the same bookmarks, in the same order when they were already grouped.
+pub fn write(out: &mut impl Write, bookmarks: &[Bookmark]) -> io::Result<()> {
+    out.write_all(HEADER.as_bytes())?;
+    Folder::from_categories(bookmarks).write(out, 0)
+}
+
+impl Folder<'_> {
+    /// Write the list of items of the folder, indented by `depth` levels.
+    fn write(&self, out: &mut impl Write, depth: usize) -> io::Result<()> {
+        let indent = "    ".repeat(depth);
+        writeln!(out, "{indent}<DL><p>")?;
+        for item in &self.items {
+            match item {
+                Item::Bookmark(bookmark) => write_bookmark(out, bookmark, depth + 1)?,
+                Item::Folder(folder) => {
+                    writeln!(out, "{indent}    <DT><H3>{}</H3>", escape(folder.name))?;
+                    folder.write(out, depth + 1)?;
+                }
+            }
+        }
+        writeln!(out, "{indent}</DL><p>")
+    }
+}
+
+fn write_bookmark(out: &mut impl Write, bookmark: &Bookmark, depth: usize) -> io::Result<()> {
+    let indent = "    ".repeat(depth);
+    write!(out, "{indent}<DT><A HREF=\"{}\"", escape(&bookmark.url))?;
+    if let Some(added) = bookmark.added {
+        write!(out, " ADD_DATE=\"{added}\"")?;
+    }
+    if let Some(modified) = bookmark.modified {
+        write!(out, " LAST_MODIFIED=\"{modified}\"")?;
+    }
+    if !bookmark.icon.is_empty() {
+        write!(out, " ICON=\"{}\"", escape(&bookmark.icon))?;
+    }
+    if !bookmark.keyword.is_empty() {
+        write!(out, " SHORTCUTURL=\"{}\"", escape(&bookmark.keyword))?;
+    }
+    if !bookmark.tags.is_empty() {
+        write!(out, " TAGS=\"{}\"", escape(&bookmark.tags.join(",")))?;
+    }
+    // Not an attribute browsers know, they ignore it.
+    if bookmark.flags != 0 {
+        write!(out, " FLAGS=\"{}\"", bookmark.flags)?;
+    }
+    writeln!(out, ">{}</A>", escape(&bookmark.title))?;
+
+    if !bookmark.description.is_empty() {
+        writeln!(out, "{indent}<DD>{}", escape(&bookmark.description))?;
+    }
+    Ok(())
+}
+
+/// Replace the characters that have a meaning in html by character references.
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Start or end tag, the names of end tags start with '/'.
+#[derive(Debug)]
+struct Tag {
+    name: String,
+    attributes: Vec<(String, String)>,
+}
+
+impl Tag {
+    /// Parse the tag `input` starts with, giving it along with the input following it.
+    fn parse(input: &str) -> Option<(Self, &str)> {
+        let mut rest = input.strip_prefix('<')?;
+        let name_end = rest
+            .find(|c: char| c.is_whitespace() || c == '>')
+            .unwrap_or(rest.len());
+        let name = rest[..name_end].trim_end_matches('/').to_ascii_lowercase();
+        if name.is_empty() {
+            return None;
+        }
+        rest = &rest[name_end..];
+
+        let mut attributes = Vec::new();
+        loop {
+            rest = rest.trim_start();
+            if rest.is_empty() {
+                break;
+            }
+            if let Some(after) = rest.strip_prefix('>') {
+                rest = after;
+                break;
+            }
+
+            let key_end = rest
+                .find(|c: char| c.is_whitespace() || matches!(c, '=' | '>'))
+                .unwrap_or(rest.len());
+            let key = rest[..key_end].to_ascii_lowercase();
+            rest = rest[key_end..].trim_start();
+
+            let mut value = String::new();
+            if let Some(after) = rest.strip_prefix('=') {
+                let after = after.trim_start();
+                let (raw, after) = match after.chars().next() {
+                    Some(quote @ ('"' | '\'')) => {
+                        let quoted = &after[1..];
+                        match quoted.find(quote) {
+                            Some(end) => (&quoted[..end], &quoted[end + 1..]),
+                            None => (quoted, ""),
+                        }
+                    }
+                    _ => {
+                        let end = after
+                            .find(|c: char| c.is_whitespace() || c == '>')
+                            .unwrap_or(after.len());
+                        after.split_at(end)
+                    }
+                };
+                value = decode(raw);
+                rest = after;
+            }
+            attributes.push((key, value));
+        }
+
+        Some((Self { name, attributes }, rest))
+    }
+
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Text being read until the next tag.
+#[derive(Debug)]
+enum Capture {
+    /// Title of a bookmark.
+    Title(Box<Bookmark>),
+    /// Name of a folder.
+    Heading,
+    /// Description of the last bookmark.
+    Description,
+}
+
+#[derive(Debug, Default)]
+struct Parser {
+    bookmarks: Vec<Bookmark>,
+    /// Names of the lists the parser is in, lists not following a heading have none.
+    folders: Vec<Option<String>>,
+    /// Name of the last heading, waiting for the list of its folder.
+    heading: Option<String>,
+    capture: Option<(Capture, String)>,
+    /// Whether a description would belong to the last bookmark.
+    describing: bool,
+}
+
+impl Parser {
+    fn text(&mut self, text: &str) {
+        if let Some((_, captured)) = &mut self.capture {
+            captured.push_str(&decode(text));
+        }
+    }
+
+    fn tag(&mut self, tag: Tag) {
+        if tag.name == "br" {
+            if let Some((Capture::Description, captured)) = &mut self.capture {
+                captured.push('\n');
+            }
+            return;
+        }
+
+        self.finish_text();
+        match tag.name.as_str() {
+            "a" => {
+                self.describing = false;
+                self.capture = self
+                    .bookmark(&tag)
+                    .map(|bookmark| (Capture::Title(Box::new(bookmark)), String::new()));
+            }
+            "h3" => {
+                self.describing = false;
+                self.capture = Some((Capture::Heading, String::new()));
+            }
+            "dd" if self.describing => self.capture = Some((Capture::Description, String::new())),
+            "dl" => {
+                self.describing = false;
+                self.folders.push(self.heading.take());
+            }
+            "/dl" => {
+                self.describing = false;
+                self.folders.pop();
+            }
+            "dt" => self.describing = false,
+            _ => (),
+        }
+    }
+
+    /// End the text being read, tags are not nested inside of the text that is read.
+    fn finish_text(&mut self) {
+        let Some((capture, text)) = self.capture.take() else {
+            return;
+        };
+        match capture {
+            Capture::Title(bookmark) => {
+                self.bookmarks.push(Bookmark {
+                    title: text,
+                    ..*bookmark
+                });
+                self.describing = true;
+            }
+            Capture::Heading => self.heading = Some(text),
+            Capture::Description => {
+                if let Some(bookmark) = self.bookmarks.last_mut() {
+                    bookmark.description = strip_line_end(&text).into();
+                }
+                self.describing = false;
+            }
+        }
+    }
+
+    /// Bookmark described by the attributes of an anchor, without its title.
+    fn bookmark(&self, tag: &Tag) -> Option<Bookmark> {
+        let url = tag.attribute("href")?.trim();
+        let tags = tag.attribute("tags").unwrap_or_default();
+        let number = |name| tag.attribute(name).and_then(|t| t.trim().parse().ok());
+        let icon = tag.attribute("icon").or_else(|| tag.attribute("icon_uri"));
+
+        Some(Bookmark {
+            added: number("add_date"),
+            modified: number("last_modified"),
+            category: self
+                .folders
+                .iter()
+                .flatten()
+                .map(String::as_str)
+                .collect::<Vec<_>>()
+                .join("/"),
+            icon: icon.unwrap_or_default().into(),
+            keyword: tag.attribute("shortcuturl").unwrap_or_default().into(),
+            flags: number("flags").unwrap_or_default(),
+            ..Bookmark::new(url, "", tags).ok()?
+        })
+    }
+}
+
+/// Remove the line break and indentation following the text of a `<DD>`, which belong to the
+/// next line of the file rather than to the description.
+fn strip_line_end(text: &str) -> &str {
+    match text.rfind('\n') {
+        Some(end) if text[end..].trim().is_empty() => {
+            let text = &text[..end];
+            text.strip_suffix('\r').unwrap_or(text)
+        }
+        _ => text,
+    }
+}
+
+/// Replace character references by the characters they stand for, unknown ones are kept.
+fn decode(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start + 1..];
+
+        let end = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '#'))
+            .unwrap_or(rest.len());
+        match rest[end..]
+            .starts_with(';')
+            .then(|| reference(&rest[..end]))
+            .flatten()
+        {
+            Some(c) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => out.push('&'),
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn reference(name: &str) -> Option<char> {
+    match name {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        "nbsp" => Some('\u{a0}'),
+        _ => {
+            let number = name.strip_prefix('#')?;
+            let code = match number.strip_prefix(['x', 'X']) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => number.parse().ok()?,
+            };
+            char::from_u32(code)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(bookmarks: &[Bookmark]) -> Vec<Bookmark> {
+        let mut html = Vec::new();
+        write(&mut html, bookmarks).unwrap();
+        parse(&String::from_utf8(html).unwrap())
+    }
+
+    #[test]
+    fn export_and_import_give_the_same_bookmarks() {
+        let bookmarks = vec![
+            Bookmark {
+                url: "https://example.com/?a=1&b=\"2\"".into(),
+                title: "  Spaced title ".into(),
+                description: "desc line\nsecond  ".into(),
+                tags: vec!["rust".into(), "two words".into()],
+                added: Some(1_680_000_000),
+                modified: Some(1_680_000_100),
+                category: "Work/Sub & <Co>".into(),
+                icon: "data:image/png;base64,AAAA".into(),
+                keyword: "ex".into(),
+                flags: 5,
+            },
+            Bookmark {
+                url: "https://example.org".into(),
+                title: "Fish & \"chips\" <b>".into(),
+                description: "ends with a line break\n".into(),
+                category: "Work".into(),
+                ..Bookmark::default()
+            },
+            Bookmark {
+                url: "https://example.net".into(),
+                ..Bookmark::default()
+            },
+            Bookmark {
+                url: "place:sort=8".into(),
+                title: "Nested".into(),
+                category: "A/B/C".into(),
+                ..Bookmark::default()
+            },
+        ];
+
+        assert_eq!(round_trip(&bookmarks), bookmarks);
+    }
+
+    #[test]
+    fn bookmarks_are_grouped_by_category() {
+        let bookmark = |url: &str, category: &str| Bookmark {
+            url: url.into(),
+            category: category.into(),
+            ..Bookmark::default()
+        };
+        let bookmarks = [
+            bookmark("https://a.example", "x"),
+            bookmark("https://b.example", ""),
+            bookmark("https://c.example", "x"),
+        ];
+
+        let urls = round_trip(&bookmarks)
+            .into_iter()
+            .map(|bookmark| bookmark.url)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            urls,
+            [
+                "https://a.example",
+                "https://c.example",
+                "https://b.example"
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_browser_exports() {
+        let html = r#"<!DOCTYPE NETSCAPE-Bookmark-file-1>
+<!-- comment with <A HREF="https://ignored.example">a link</A> -->
+<H1>Bookmarks Menu</H1>
+<DL><p>
+    <DT><A HREF="https://one.example" ADD_DATE="1" LAST_MODIFIED="2" ICON_URI="https://one.example/icon" TAGS="a,b" SHORTCUTURL="one">One &amp; &#x41;&#66;</A>
+    <DD>First<BR>second &unknown;
+    <DT><H3 ADD_DATE="1">Folder</H3>
+    <DD>Folder descriptions are ignored
+    <DL><p>
+        <DT><A HREF=https://two.example>Two</A>
+        <DT><A HREF="not a url">Skipped</A>
+    </DL><p>
+</DL><p>
+"#;
+
+        assert_eq!(
+            parse(html),
+            [
+                Bookmark {
+                    url: "https://one.example".into(),
+                    title: "One & AB".into(),
+                    description: "First\nsecond &unknown;".into(),
+                    tags: vec!["a".into(), "b".into()],
+                    added: Some(1),
+                    modified: Some(2),
+                    icon: "https://one.example/icon".into(),
+                    keyword: "one".into(),
+                    ..Bookmark::default()
+                },
+                Bookmark {
+                    url: "https://two.example".into(),
+                    title: "Two".into(),
+                    category: "Folder".into(),
+                    ..Bookmark::default()
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,133 @@
+use std::{
+    env,
+    process::{Command, Stdio},
+    thread,
+};
+
+use crate::{command_line::CommandLine, Result};
+
+/// Placeholder replaced by the url in opener arguments.
+const URL_PLACEHOLDER: &str = "%s";
+
+/// Command used to open urls.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Opener {
+    command: CommandLine,
+}
+
+impl Opener {
+    /// Create an opener from a command line, the url is passed in place of any "%s" argument or
+    /// appended if there is none.
+    pub fn new(command: &str) -> Option<Self> {
+        CommandLine::parse(command).map(|command| Self { command })
+    }
+
+    /// Opener given by the first entry of `$BROWSER` with `xdg-open` as fallback.
+    pub fn from_env() -> Self {
+        env::var("BROWSER")
+            .ok()
+            .and_then(|browser| browser.split(':').find_map(Self::new))
+            .unwrap_or_else(|| Self {
+                command: CommandLine::program("xdg-open"),
+            })
+    }
+
+    fn command(&self, url: &str) -> Command {
+        let mut command = Command::new(&self.command.program);
+
+        let mut has_placeholder = false;
+        for arg in &self.command.args {
+            if arg.contains(URL_PLACEHOLDER) {
+                has_placeholder = true;
+                command.arg(arg.replace(URL_PLACEHOLDER, url));
+            } else {
+                command.arg(arg);
+            }
+        }
+
+        if !has_placeholder {
+            command.arg(url);
+        }
+
+        command
+    }
+
+    /// Open the url without waiting for the opener to finish.
+    ///
+    /// The opener gets no access to the terminal and is put in its own process group so that it
+    /// survives the tui exiting.
+    pub fn open(&self, url: &str) -> Result<()> {
+        let mut command = self.command(url);
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(&mut command, 0);
+
+        let mut child = command
+            .spawn()
+            .map_err(|source| self.command.open_error(source))?;
+
+        // Reap the child once it exits so that it does not linger as a zombie.
+        thread::spawn(move || child.wait());
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Error;
+
+    fn args(opener: &Opener, url: &str) -> Vec<String> {
+        opener
+            .command(url)
+            .get_args()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn url_replaces_placeholder() {
+        let opener = Opener::new("browser --new-tab=%s %s -x").unwrap();
+        assert_eq!(
+            args(&opener, "https://a.b"),
+            ["--new-tab=https://a.b", "https://a.b", "-x"]
+        );
+    }
+
+    #[test]
+    fn url_is_appended_without_placeholder() {
+        let opener = Opener::new("  browser  --new-tab ").unwrap();
+        assert_eq!(opener.command.program, "browser");
+        assert_eq!(args(&opener, "https://a.b"), ["--new-tab", "https://a.b"]);
+        assert_eq!(Opener::new("   "), None);
+    }
+
+    #[test]
+    fn opens_with_stub_command() {
+        let dir = tempfile::tempdir().unwrap();
+        let opened = dir.path().join("opened");
+        let url = opened.to_str().unwrap();
+
+        // The stub creates the file named by the url, run here waiting for it to exit.
+        let opener = Opener::new("touch %s").unwrap();
+        assert!(opener.command(url).status().unwrap().success());
+        assert!(opened.exists());
+        opener.open(url).unwrap();
+    }
+
+    #[test]
+    fn spawn_failure_is_open_error() {
+        let opener = Opener::new("/nonexistent/bookmark-tui-browser").unwrap();
+        match opener.open("https://a.b") {
+            Err(Error::Open { program, .. }) => {
+                assert_eq!(program, "/nonexistent/bookmark-tui-browser")
+            }
+            result => panic!("expected an open error, got {result:?}"),
+        }
+    }
+}
@@ -0,0 +1,307 @@
+use std::ops::Range;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{
+    bookmark::Entry,
+    layout::{entry_rows, overflow, str_width, Align, Truncate},
+    prompt::Prompt,
+    screen::Screen,
+    width::fit_width,
+    Result,
+};
+use crossterm::style::{Color, ContentStyle, Stylize};
+
+pub fn entry_style(entry: &Entry) -> ContentStyle {
+    match entry {
+        Entry::Bookmark(_) => ContentStyle::new(),
+        Entry::Comment(_) => ContentStyle::new().with(Color::DarkGrey),
+        Entry::Invalid { .. } => ContentStyle::new().with(Color::Red),
+    }
+}
+
+pub fn match_style() -> ContentStyle {
+    ContentStyle::new().with(Color::Black).on(Color::Yellow)
+}
+
+pub fn status_style() -> ContentStyle {
+    ContentStyle::new().reverse()
+}
+
+/// Row of the screen showing an entry or part of it.
+#[derive(Debug, Clone)]
+pub struct Row {
+    /// Index of the entry from the first one laid out.
+    pub entry: usize,
+    pub text: String,
+    pub style: ContentStyle,
+}
+
+/// Width labels of input fields are padded to.
+const FIELD_LABEL_WIDTH: usize = 5;
+
+/// Rows filling the screen.
+#[derive(Debug, Clone, Default)]
+pub struct Layout {
+    pub rows: Vec<Row>,
+    /// Amount of entries shown in full.
+    pub complete: usize,
+    /// Length in grapheme clusters of the longest entry shown.
+    pub longest: usize,
+    /// Whether the marquee entry is too wide to be shown in full.
+    pub marquee_overflows: bool,
+}
+
+/// Frames the marquee rests at either end of a line.
+const MARQUEE_PAUSE: usize = 10;
+
+/// Lay out entries as rows filling the given height, scrolled `h_scroll` grapheme clusters to the
+/// right. The entry given by `marquee` as an index and a step scrolls through its full content
+/// instead.
+pub fn layout_rows(
+    entries: impl IntoIterator<Item = Result<Entry>>,
+    truncate: Truncate,
+    h_scroll: usize,
+    marquee: Option<(usize, usize)>,
+    (term_width, term_height): (u16, u16),
+) -> Result<Layout> {
+    let height = term_height as usize;
+    let width = term_width as usize;
+    let mut layout = Layout {
+        rows: Vec::with_capacity(height),
+        ..Layout::default()
+    };
+
+    for (index, entry) in entries.into_iter().enumerate() {
+        if layout.rows.len() >= height {
+            break;
+        }
+
+        let entry = entry?;
+        let label = entry.label();
+        layout.longest = layout.longest.max(label.graphemes(true).count());
+
+        let mut offset = h_scroll;
+        if let Some((_, step)) = marquee.filter(|(marquee, _)| *marquee == index) {
+            let overflow = overflow(&label.replace(char::is_control, " "), width);
+            if overflow != 0 && truncate != Truncate::Wrap {
+                layout.marquee_overflows = true;
+                let period = overflow + 2 * MARQUEE_PAUSE;
+                offset = (step % period).saturating_sub(MARQUEE_PAUSE).min(overflow);
+            }
+        }
+
+        let style = entry_style(&entry);
+        let entry_rows = entry_rows(&entry, truncate, width, offset);
+        if layout.rows.len() + entry_rows.len() <= height {
+            layout.complete += 1;
+        }
+
+        layout.rows.extend(entry_rows.into_iter().map(|text| Row {
+            entry: index,
+            text,
+            style,
+        }));
+    }
+
+    layout.rows.truncate(height);
+    Ok(layout)
+}
+
+/// Draw rows, `highlight` gives byte ranges of the text of each row to be highlighted.
+pub fn draw_rows(
+    screen: &mut Screen,
+    rows: &[Row],
+    selected_entry: usize,
+    highlight: impl Fn(&str) -> Vec<Range<usize>>,
+    align: Align,
+    (term_width, _): (u16, u16),
+) {
+    for (index, row) in rows.iter().enumerate() {
+        let is_selected = row.entry == selected_entry;
+        draw_line(
+            screen,
+            &row.text,
+            &highlight(&row.text),
+            if is_selected {
+                row.style.reverse()
+            } else {
+                row.style
+            },
+            is_selected,
+            align,
+            index as u16,
+            term_width as usize,
+        );
+    }
+}
+
+/// Draw a line that fits in the given width, filled lines have the padding drawn using the style
+/// as well.
+#[allow(clippy::too_many_arguments)]
+pub fn draw_line(
+    screen: &mut Screen,
+    line: &str,
+    highlights: &[Range<usize>],
+    style: ContentStyle,
+    fill: bool,
+    align: Align,
+    row: u16,
+    max_width: usize,
+) {
+    let width = str_width(line);
+    let left = align.offset(width, max_width);
+    let right = max_width.saturating_sub(width + left);
+
+    if fill && left != 0 {
+        screen.print(0, row, &" ".repeat(left), style);
+    }
+
+    let column = draw_highlighted(screen, left as u16, row, line, highlights, style);
+
+    if fill && right != 0 {
+        screen.print(column, row, &" ".repeat(right), style);
+    }
+}
+
+/// Draw the line with the given byte ranges drawn using [match_style], returns the column after
+/// the line.
+fn draw_highlighted(
+    screen: &mut Screen,
+    mut column: u16,
+    row: u16,
+    line: &str,
+    highlights: &[Range<usize>],
+    style: ContentStyle,
+) -> u16 {
+    let mut pos = 0;
+    for range in highlights {
+        if range.end <= pos {
+            continue;
+        }
+
+        if range.start >= line.len() {
+            break;
+        }
+
+        let range_start = range.start.max(pos);
+        let range_end = range.end.min(line.len());
+        if range_start > pos {
+            column = screen.print(column, row, &line[pos..range_start], style);
+        }
+        column = screen.print(column, row, &line[range_start..range_end], match_style());
+        pos = range_end;
+    }
+
+    if pos < line.len() {
+        column = screen.print(column, row, &line[pos..], style);
+    }
+
+    column
+}
+
+/// Draw the status line over the given row, `right` is kept in full when possible and `left` is
+/// cut to fit next to it. A text cursor is drawn at the byte index `cursor` of `left`.
+#[allow(clippy::too_many_arguments)]
+pub fn draw_status(
+    screen: &mut Screen,
+    left: &str,
+    left_style: ContentStyle,
+    cursor: Option<usize>,
+    right: &str,
+    row: u16,
+    max_width: usize,
+) {
+    let style = status_style();
+    screen.print(0, row, &" ".repeat(max_width), style);
+
+    let right_width = str_width(right);
+    let left_end = fit_width(left, max_width.saturating_sub(right_width + 1));
+    screen.print(0, row, &left[..left_end], left_style);
+    if let Some(cursor) = cursor.filter(|cursor| *cursor < left_end) {
+        draw_cursor(
+            screen,
+            0,
+            &left[..left_end],
+            cursor,
+            row,
+            ContentStyle::new(),
+        );
+    }
+    screen.print(
+        max_width.saturating_sub(right_width) as u16,
+        row,
+        right,
+        style,
+    );
+}
+
+/// Draw an input field over the given row, the focused field shows a text cursor.
+pub fn draw_field(
+    screen: &mut Screen,
+    label: &str,
+    prompt: &Prompt,
+    focused: bool,
+    row: u16,
+    max_width: usize,
+) {
+    let label_style = if focused {
+        ContentStyle::new().bold()
+    } else {
+        ContentStyle::new().with(Color::DarkGrey)
+    };
+    screen.print(0, row, &" ".repeat(max_width), ContentStyle::new());
+    let column = screen.print(
+        0,
+        row,
+        &format!("{label:>FIELD_LABEL_WIDTH$}: "),
+        label_style,
+    );
+
+    // The text is scrolled to keep the cursor in view, a space is added for it to be drawn on
+    // past the end of the text.
+    let text = format!("{} ", prompt.text());
+    let cursor_end = grapheme_end(&text, prompt.cursor());
+    let available = max_width.saturating_sub(column as usize);
+    let mut start = 0;
+    while str_width(&text[start..cursor_end]) > available {
+        start = grapheme_end(&text, start);
+    }
+    let text = &text[start..start + fit_width(&text[start..], available)];
+    screen.print(column, row, text, ContentStyle::new());
+
+    if focused && start <= prompt.cursor() {
+        let cursor = prompt.cursor() - start;
+        draw_cursor(
+            screen,
+            column,
+            text,
+            cursor,
+            row,
+            ContentStyle::new().reverse(),
+        );
+    }
+}
+
+/// Draw the grapheme cluster at the byte index `cursor` of a text drawn from the given column.
+fn draw_cursor(
+    screen: &mut Screen,
+    column: u16,
+    text: &str,
+    cursor: usize,
+    row: u16,
+    style: ContentStyle,
+) {
+    let column = column as usize + str_width(&text[..cursor]);
+    screen.print(
+        column as u16,
+        row,
+        &text[cursor..grapheme_end(text, cursor)],
+        style,
+    );
+}
+
+/// Byte index of the end of the grapheme cluster starting at the given index.
+fn grapheme_end(text: &str, start: usize) -> usize {
+    start + text[start..].graphemes(true).next().map_or(0, str::len)
+}
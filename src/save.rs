@@ -0,0 +1,157 @@
+use std::{
+    ffi::OsString,
+    fs::{self, File, OpenOptions},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    process,
+};
+
+use crate::Result;
+
+/// Replace the file at the given path with what `write` writes, so that the file is never seen
+/// partially written.
+///
+/// The content is written to a temporary file in the same directory which is synced to disk and
+/// renamed over the original. The permissions of the original are kept and with `backup` it is
+/// copied to a file with ".bak" appended to its name first.
+pub fn write_atomic(
+    path: &Path,
+    backup: bool,
+    write: impl FnOnce(&mut BufWriter<&File>) -> Result<()>,
+) -> Result<()> {
+    // Writing through a symlink replaces its target rather than the link.
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.into());
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+
+    let mut temp_name = OsString::from(".");
+    temp_name.push(path.file_name().unwrap_or_default());
+    temp_name.push(format!(".{}.tmp", process::id()));
+    let temp_path = dir.join(temp_name);
+
+    let file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&temp_path)?;
+    let result = replace_with(&file, &temp_path, &path, dir, backup, write);
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result
+}
+
+fn replace_with(
+    file: &File,
+    temp_path: &Path,
+    path: &Path,
+    dir: &Path,
+    backup: bool,
+    write: impl FnOnce(&mut BufWriter<&File>) -> Result<()>,
+) -> Result<()> {
+    let mut writer = BufWriter::new(file);
+    write(&mut writer)?;
+    writer.flush()?;
+    drop(writer);
+
+    if let Ok(metadata) = fs::metadata(path) {
+        file.set_permissions(metadata.permissions())?;
+        if backup {
+            fs::copy(path, backup_path(path))?;
+        }
+    }
+    file.sync_all()?;
+
+    fs::rename(temp_path, path)?;
+    // The rename itself is only durable once the directory is synced.
+    File::open(dir)?.sync_all()?;
+
+    Ok(())
+}
+
+fn backup_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".bak");
+    name.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::*;
+
+    fn write_text(path: &Path, backup: bool, text: &str) -> Result<()> {
+        write_atomic(
+            path,
+            backup,
+            |writer| Ok(writer.write_all(text.as_bytes())?),
+        )
+    }
+
+    #[test]
+    fn file_is_replaced_keeping_permissions() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bookmarks.txt");
+        fs::write(&path, "old\n").unwrap();
+        let mut permissions = fs::metadata(&path).unwrap().permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(&path, permissions.clone()).unwrap();
+
+        write_text(&path, false, "new\n").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new\n");
+        assert_eq!(fs::metadata(&path).unwrap().permissions(), permissions);
+        assert!(!backup_path(&path).exists());
+    }
+
+    #[test]
+    fn backup_keeps_the_previous_content() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bookmarks.txt");
+        write_text(&path, true, "first\n").unwrap();
+        // There is nothing to back up for a new file.
+        assert!(!backup_path(&path).exists());
+
+        write_text(&path, true, "second\n").unwrap();
+        write_text(&path, true, "third\n").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "third\n");
+        assert_eq!(fs::read_to_string(backup_path(&path)).unwrap(), "second\n");
+    }
+
+    #[test]
+    fn failed_write_leaves_the_file_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bookmarks.txt");
+        fs::write(&path, "old\n").unwrap();
+
+        let result = write_atomic(&path, true, |writer| {
+            writer.write_all(b"partial")?;
+            Err(io::Error::other("failed").into())
+        });
+        assert!(result.is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old\n");
+        let names = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["bookmarks.txt"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_target_is_replaced() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("target.txt");
+        let link = dir.path().join("link.txt");
+        fs::write(&target, "old\n").unwrap();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        write_text(&link, false, "new\n").unwrap();
+        assert!(fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "new\n");
+    }
+}
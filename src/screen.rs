@@ -0,0 +1,201 @@
+use std::{io::Write, mem};
+
+use crossterm::{
+    cursor::MoveTo,
+    style::{ContentStyle, PrintStyledContent},
+    terminal::{Clear, ClearType},
+    QueueableCommand,
+};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{width::grapheme_width, Result};
+
+/// Single terminal cell, the cell after a wide grapheme cluster has an empty symbol.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Cell {
+    symbol: String,
+    style: ContentStyle,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Self {
+            symbol: " ".into(),
+            style: ContentStyle::new(),
+        }
+    }
+}
+
+/// Double buffered screen, frames are drawn to a buffer and only the cells that differ from the
+/// previous frame are sent to the terminal.
+#[derive(Debug)]
+pub struct Screen {
+    size: (u16, u16),
+    cells: Vec<Cell>,
+    /// Last frame sent to the terminal, unknown before the first frame and after a resize.
+    previous: Option<Vec<Cell>>,
+}
+
+impl Screen {
+    pub fn new(size: (u16, u16)) -> Self {
+        Self {
+            size,
+            cells: blank(size),
+            previous: None,
+        }
+    }
+
+    pub fn resize(&mut self, size: (u16, u16)) {
+        self.size = size;
+        self.cells = blank(size);
+        self.invalidate();
+    }
+
+    /// Forget what the terminal shows, the next frame is drawn in full.
+    pub fn invalidate(&mut self) {
+        self.previous = None;
+    }
+
+    /// Draw text starting at the given cell, cut at the right edge. Returns the column after the
+    /// text.
+    pub fn print(&mut self, column: u16, row: u16, text: &str, style: ContentStyle) -> u16 {
+        let (width, height) = self.size;
+        if row >= height {
+            return column;
+        }
+
+        let mut column = column as usize;
+        for grapheme in text.graphemes(true) {
+            let grapheme_width = grapheme_width(grapheme);
+            if grapheme_width == 0 {
+                continue;
+            }
+            if column + grapheme_width > width as usize {
+                break;
+            }
+
+            let row_start = row as usize * width as usize;
+            let index = row_start + column;
+            // Wide grapheme clusters partly covered by this one are blanked, the terminal does the
+            // same.
+            let mut lead = index;
+            while lead > row_start && self.cells[lead].symbol.is_empty() {
+                lead -= 1;
+            }
+            self.cells[lead..index].fill(Cell::default());
+            let mut next = index + grapheme_width;
+            while next < row_start + width as usize && self.cells[next].symbol.is_empty() {
+                self.cells[next] = Cell::default();
+                next += 1;
+            }
+
+            self.cells[index] = Cell {
+                symbol: grapheme.into(),
+                style,
+            };
+            for cell in &mut self.cells[index + 1..index + grapheme_width] {
+                *cell = Cell {
+                    symbol: String::new(),
+                    style,
+                };
+            }
+            column += grapheme_width;
+        }
+
+        column as u16
+    }
+
+    /// Send the cells changed since the last frame and start a new blank frame.
+    pub fn flush(&mut self, mut writer: impl Write) -> Result<()> {
+        let width = self.size.0 as usize;
+        if self.previous.is_none() {
+            writer.queue(Clear(ClearType::All))?;
+        }
+
+        // Changed cells are sent in runs sharing a style, moving the cursor only between gaps.
+        let blank_cell = Cell::default();
+        let mut run = String::new();
+        let mut run_style = ContentStyle::new();
+        let mut terminal_pos = None;
+        for (index, cell) in self.cells.iter().enumerate() {
+            let previous = self.previous.as_ref().map_or(&blank_cell, |p| &p[index]);
+            if cell.symbol.is_empty() || cell == previous {
+                continue;
+            }
+
+            let pos = ((index % width) as u16, (index / width) as u16);
+            if terminal_pos != Some(pos) || cell.style != run_style {
+                queue_run(&mut writer, &mut run, run_style)?;
+                run_style = cell.style;
+            }
+            if terminal_pos != Some(pos) {
+                writer.queue(MoveTo(pos.0, pos.1))?;
+            }
+
+            run.push_str(&cell.symbol);
+            terminal_pos = Some((pos.0 + grapheme_width(&cell.symbol) as u16, pos.1));
+        }
+        queue_run(&mut writer, &mut run, run_style)?;
+        writer.flush()?;
+
+        self.previous = Some(mem::replace(&mut self.cells, blank(self.size)));
+        Ok(())
+    }
+}
+
+fn blank((width, height): (u16, u16)) -> Vec<Cell> {
+    vec![Cell::default(); width as usize * height as usize]
+}
+
+fn queue_run(mut writer: impl Write, run: &mut String, style: ContentStyle) -> Result<()> {
+    if !run.is_empty() {
+        writer.queue(PrintStyledContent(style.apply(mem::take(run))))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(screen: &mut Screen, texts: &[(u16, &str)]) -> String {
+        for &(column, text) in texts {
+            screen.print(column, 0, text, ContentStyle::new());
+        }
+        let mut out = Vec::new();
+        screen.flush(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    fn symbols(screen: &Screen) -> Vec<&str> {
+        let cells = screen.previous.as_ref().unwrap();
+        cells.iter().map(|cell| cell.symbol.as_str()).collect()
+    }
+
+    #[test]
+    fn unchanged_frame_sends_nothing() {
+        let mut screen = Screen::new((6, 2));
+        assert!(!frame(&mut screen, &[(0, "ab漢")]).is_empty());
+        assert_eq!(frame(&mut screen, &[(0, "ab漢")]), "");
+    }
+
+    #[test]
+    fn narrow_text_over_a_wide_grapheme_blanks_the_rest_of_it() {
+        let mut screen = Screen::new((4, 1));
+        frame(&mut screen, &[(0, "漢字")]);
+
+        // Only the continuation cell of the first one is covered.
+        let out = frame(&mut screen, &[(0, "漢字"), (1, "x")]);
+        assert_eq!(symbols(&screen), [" ", "x", "字", ""]);
+        assert!(out.contains(" x"), "{out:?}");
+
+        // Only the first cell of the second one is covered.
+        let out = frame(&mut screen, &[(0, "漢字"), (2, "y")]);
+        assert_eq!(symbols(&screen), ["漢", "", "y", " "]);
+        assert!(out.contains("漢y "), "{out:?}");
+
+        let out = frame(&mut screen, &[(0, "ab")]);
+        assert_eq!(symbols(&screen), ["a", "b", " ", " "]);
+        assert!(out.contains("ab"), "{out:?}");
+    }
+}
@@ -0,0 +1,158 @@
+use std::ops::Range;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{bookmark::Entry, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Forward,
+    Backward,
+}
+
+/// Case insensitive search query matched one grapheme cluster at a time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Query {
+    text: String,
+    graphemes: Vec<String>,
+}
+
+impl Query {
+    /// Create a query, empty queries match nothing and are rejected.
+    pub fn new(text: &str) -> Option<Self> {
+        if text.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            text: text.into(),
+            graphemes: text.graphemes(true).map(str::to_lowercase).collect(),
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    /// Byte ranges of all non overlapping matches in the haystack.
+    pub fn find_iter(&self, haystack: &str) -> Vec<Range<usize>> {
+        let graphemes = haystack
+            .grapheme_indices(true)
+            .map(|(i, g)| (i, g.to_lowercase()))
+            .collect::<Vec<_>>();
+
+        let len = self.graphemes.len();
+        let mut matches = Vec::new();
+        let mut start = 0;
+        while start + len <= graphemes.len() {
+            let window = &graphemes[start..start + len];
+            if window.iter().zip(&self.graphemes).all(|((_, a), b)| a == b) {
+                let end = graphemes
+                    .get(start + len)
+                    .map_or(haystack.len(), |(i, _)| *i);
+                matches.push(graphemes[start].0..end);
+                start += len;
+            } else {
+                start += 1;
+            }
+        }
+
+        matches
+    }
+
+    pub fn is_match(&self, haystack: &str) -> bool {
+        !self.find_iter(haystack).is_empty()
+    }
+
+    /// Find the index of the closest matching entry from the given index in the given direction,
+    /// the index itself included. The search wraps around the ends of the entries.
+    pub fn find(
+        &self,
+        entries: impl IntoIterator<Item = Result<Entry>>,
+        from: usize,
+        direction: Direction,
+    ) -> Result<Option<usize>> {
+        let mut first = None;
+        let mut last_before = None;
+        let mut last = None;
+
+        for (line, entry) in entries.into_iter().enumerate() {
+            if !self.is_match(&entry?.label()) {
+                continue;
+            }
+
+            if direction == Direction::Forward && line >= from {
+                return Ok(Some(line));
+            }
+
+            first.get_or_insert(line);
+            if line <= from {
+                last_before = Some(line);
+            }
+            last = Some(line);
+        }
+
+        Ok(match direction {
+            Direction::Forward => first,
+            Direction::Backward => last_before.or(last),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Matched byte ranges as pairs of start and end.
+    fn matches(query: &str, text: &str) -> Vec<(usize, usize)> {
+        Query::new(query)
+            .unwrap()
+            .find_iter(text)
+            .into_iter()
+            .map(|range| (range.start, range.end))
+            .collect()
+    }
+
+    fn entries(lines: &[&str]) -> Vec<Result<Entry>> {
+        lines.iter().map(|line| Ok(Entry::parse(line))).collect()
+    }
+
+    #[test]
+    fn combining_marks_are_part_of_the_character() {
+        assert!(matches("e", "cafe\u{301}").is_empty());
+        assert_eq!(matches("e\u{301}", "CAFE\u{301}"), [(3, 6)]);
+        assert_eq!(matches("caf", "cafe\u{301}"), [(0, 3)]);
+    }
+
+    #[test]
+    fn case_is_ignored_beyond_ascii() {
+        assert_eq!(matches("straße", "STRAßE"), [(0, 7)]);
+        assert_eq!(matches("ÄÖ", "xäöäö"), [(1, 5), (5, 9)]);
+        assert_eq!(matches("σ", "ΣΑΣ"), [(0, 2), (4, 6)]);
+        assert_eq!(matches("aa", "aaa"), [(0, 2)]);
+        assert_eq!(Query::new(""), None);
+    }
+
+    #[test]
+    fn find_wraps_around_in_both_directions() {
+        let lines = ["# match", "# other", "# match", "# other"];
+        let query = Query::new("MATCH").unwrap();
+        let find = |from, direction| query.find(entries(&lines), from, direction).unwrap();
+
+        assert_eq!(find(0, Direction::Forward), Some(0));
+        assert_eq!(find(1, Direction::Forward), Some(2));
+        assert_eq!(find(3, Direction::Forward), Some(0));
+        assert_eq!(find(3, Direction::Backward), Some(2));
+        assert_eq!(find(1, Direction::Backward), Some(0));
+        assert_eq!(find(usize::MAX, Direction::Backward), Some(2));
+        // Nothing before the first line, so the search goes on from the end.
+        let wrapped = query.find(entries(&lines[1..]), 0, Direction::Backward);
+        assert_eq!(wrapped.unwrap(), Some(1));
+
+        let query = Query::new("nowhere").unwrap();
+        assert_eq!(
+            query.find(entries(&lines), 0, Direction::Forward).unwrap(),
+            None
+        );
+    }
+}
@@ -0,0 +1,461 @@
+use std::{
+    fs::{self, File, Metadata},
+    io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write},
+    iter::FusedIterator,
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver, TryRecvError},
+    thread,
+    time::SystemTime,
+};
+
+use tap::Pipe;
+
+use crate::{bookmark::Entry, save::write_atomic, Error, Result};
+
+/// Bookmark file read on demand.
+pub struct Source {
+    path: PathBuf,
+    /// State of the file when it was last read or written.
+    stamp: Stamp,
+    reader: BufReader<File>,
+    /// Byte offsets of the start of every line found so far. Once the index is complete the last
+    /// offset is the end of the file.
+    offsets: Vec<u64>,
+    complete: bool,
+    /// Complete index being built in the background.
+    pending: Option<Receiver<io::Result<Vec<u64>>>>,
+}
+
+impl Source {
+    /// Open a file, the full line index is built in the background while the lines needed are
+    /// indexed on demand.
+    pub fn open(path: &Path) -> Result<Self> {
+        let mut reader = File::open(path)?.pipe(BufReader::new);
+        let stamp = Stamp::new(&reader.get_ref().metadata()?);
+        let start_pos = reader.stream_position()?;
+
+        let (sender, receiver) = mpsc::channel();
+        let mut background = File::open(path)?.pipe(BufReader::new);
+        thread::spawn(move || {
+            let mut offsets = vec![start_pos];
+            let result = background
+                .seek(SeekFrom::Start(start_pos))
+                .and_then(|_| index_lines(&mut background, &mut offsets, usize::MAX))
+                .map(|_| offsets);
+            // The source may be gone by the time indexing is done.
+            let _ = sender.send(result);
+        });
+
+        Ok(Self {
+            path: path.into(),
+            stamp,
+            reader,
+            offsets: vec![start_pos],
+            complete: false,
+            pending: Some(receiver),
+        })
+    }
+
+    /// Open the file again after it was changed by another program.
+    pub fn reload(&mut self) -> Result<()> {
+        *self = Self::open(&self.path)?;
+        Ok(())
+    }
+
+    /// Whether the file was replaced or written to since it was read, files that cannot be
+    /// checked are assumed to be unchanged.
+    pub fn changed_on_disk(&self) -> bool {
+        fs::metadata(&self.path).is_ok_and(|metadata| Stamp::new(&metadata) != self.stamp)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Take the index built in the background if it is done, returns true if the index changed.
+    pub fn poll_index(&mut self) -> Result<bool> {
+        let Some(receiver) = &self.pending else {
+            return Ok(false);
+        };
+
+        match receiver.try_recv() {
+            Ok(offsets) => {
+                self.pending = None;
+                let offsets = offsets?;
+                if self.complete {
+                    return Ok(false);
+                }
+                self.offsets = offsets;
+                self.complete = true;
+                Ok(true)
+            }
+            Err(TryRecvError::Empty) => Ok(false),
+            Err(TryRecvError::Disconnected) => {
+                self.pending = None;
+                Ok(false)
+            }
+        }
+    }
+
+    /// Amount of lines in the file, known once the index is complete.
+    pub fn line_count(&self) -> Option<usize> {
+        self.complete.then(|| self.offsets.len() - 1)
+    }
+
+    /// Index the whole file without waiting for the background index, giving the amount of lines.
+    pub fn index_all(&mut self) -> Result<usize> {
+        self.index_to(usize::MAX)?;
+        Ok(self.offsets.len() - 1)
+    }
+
+    /// Extend the index until the start of the given line is known or the end of the file is
+    /// reached.
+    pub fn index_to(&mut self, line: usize) -> Result<()> {
+        if self.complete || line < self.offsets.len() {
+            return Ok(());
+        }
+
+        let pos = *self
+            .offsets
+            .last()
+            .expect("index always contains the start offset");
+        self.reader.seek(SeekFrom::Start(pos))?;
+        self.complete = index_lines(&mut self.reader, &mut self.offsets, line)?;
+
+        Ok(())
+    }
+
+    /// Text of a range of lines without their line breaks.
+    pub fn read_lines(&mut self, range: Range<usize>) -> Result<Vec<String>> {
+        self.index_to(range.end)?;
+        let start = self.offsets[range.start.min(self.offsets.len() - 1)];
+        self.reader.seek(SeekFrom::Start(start))?;
+
+        let mut lines = Vec::with_capacity(range.len());
+        for _ in range {
+            let mut line = String::new();
+            if self.reader.read_line(&mut line)? == 0 {
+                break;
+            }
+            if line.ends_with('\n') {
+                line.pop();
+            }
+            lines.push(line);
+        }
+        Ok(lines)
+    }
+
+    /// Replace a range of lines with the given ones and save the file, which has to be unchanged
+    /// since it was read.
+    pub fn replace_lines(
+        &mut self,
+        range: Range<usize>,
+        lines: &[String],
+        backup: bool,
+    ) -> Result<()> {
+        // The offsets and the reader belong to the old file, writing would lose the changes.
+        if self.changed_on_disk() {
+            return Err(Error::EditConflict);
+        }
+        let line_count = self.index_all()?;
+        if range.start > range.end || range.end > line_count {
+            return Err(Error::PastEnd(range.end));
+        }
+        let start = self.offsets[range.start];
+        let end = self.offsets[range.end];
+        let file_end = *self
+            .offsets
+            .last()
+            .expect("index always contains the start offset");
+
+        // A last line without a line break gets one when lines are added after it.
+        let mut missing_break = false;
+        if start != 0 && start == file_end && !lines.is_empty() {
+            let mut last = [0];
+            self.reader.seek(SeekFrom::Start(start - 1))?;
+            self.reader.read_exact(&mut last)?;
+            missing_break = last != *b"\n";
+        }
+
+        let reader = &mut self.reader;
+        write_atomic(&self.path, backup, |writer| {
+            reader.seek(SeekFrom::Start(0))?;
+            io::copy(&mut reader.by_ref().take(start), writer)?;
+            if missing_break {
+                writer.write_all(b"\n")?;
+            }
+            for line in lines {
+                writer.write_all(line.as_bytes())?;
+                writer.write_all(b"\n")?;
+            }
+            reader.seek(SeekFrom::Start(end))?;
+            io::copy(reader, writer)?;
+            Ok(())
+        })?;
+
+        // The old file was replaced, so the offsets of lines after the range are moved by the
+        // change in length.
+        let mut pos = start + missing_break as u64;
+        let mut offsets = self.offsets[..range.start].to_vec();
+        offsets.push(pos);
+        for line in lines {
+            pos += line.len() as u64 + 1;
+            offsets.push(pos);
+        }
+        offsets.extend(self.offsets[range.end + 1..].iter().map(|o| o - end + pos));
+        self.offsets = offsets;
+        self.reader = File::open(&self.path)?.pipe(BufReader::new);
+        self.stamp = Stamp::new(&self.reader.get_ref().metadata()?);
+
+        Ok(())
+    }
+
+    /// Entries starting at the given line, only the lines read are visited.
+    pub fn entries_from(
+        &mut self,
+        line: usize,
+    ) -> Result<impl Iterator<Item = Result<Entry>> + '_> {
+        self.index_to(line)?;
+
+        // Past the end of the file nothing is read from the end offset.
+        let offset = self.offsets[line.min(self.offsets.len() - 1)];
+        self.reader.seek(SeekFrom::Start(offset))?;
+
+        Ok(self
+            .reader
+            .ref_lines()
+            .map(|line| line.map(|line| Entry::parse(&line))))
+    }
+}
+
+/// Metadata that changes when a file is written to or replaced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Stamp {
+    len: u64,
+    modified: Option<SystemTime>,
+    #[cfg(unix)]
+    inode: u64,
+}
+
+impl Stamp {
+    fn new(metadata: &Metadata) -> Self {
+        Self {
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+            #[cfg(unix)]
+            inode: std::os::unix::fs::MetadataExt::ino(metadata),
+        }
+    }
+}
+
+/// Push the offsets of lines read from the reader until the given line is indexed, the reader is
+/// expected to be positioned at the last offset. Lines are skipped without being decoded or
+/// stored. Returns true if the end was reached.
+fn index_lines(reader: &mut impl BufRead, offsets: &mut Vec<u64>, line: usize) -> io::Result<bool> {
+    let mut pos = *offsets
+        .last()
+        .expect("index always contains the start offset");
+    while offsets.len() <= line {
+        match reader.skip_until(b'\n')? {
+            0 => return Ok(true),
+            read => {
+                pos += read as u64;
+                offsets.push(pos);
+            }
+        }
+    }
+    Ok(false)
+}
+
+enum RefLineIter<'a, R: ?Sized> {
+    Dead,
+    Alive(&'a mut R),
+}
+
+impl<'a, R> Iterator for RefLineIter<'a, R>
+where
+    R: BufRead + ?Sized,
+{
+    type Item = Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Self::Alive(reader) = self {
+            let mut buf = String::new();
+            match reader.read_line(&mut buf) {
+                Err(e) => Some(Err(Error::from(e))),
+                Ok(0) => {
+                    *self = Self::Dead;
+                    None
+                }
+                Ok(_) => Some(Ok(buf)),
+            }
+        } else {
+            None
+        }
+    }
+}
+
+impl<'a, R> FusedIterator for RefLineIter<'a, R> where R: BufRead + ?Sized {}
+
+trait BufReadRefLineExt: BufRead {
+    fn ref_lines(&mut self) -> RefLineIter<'_, Self> {
+        RefLineIter::Alive(self)
+    }
+}
+
+impl<T: BufRead> BufReadRefLineExt for T {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|&line| line.into()).collect()
+    }
+
+    /// Offsets of a complete index of the file as it is on disk.
+    fn fresh_offsets(path: &Path) -> Vec<u64> {
+        let mut source = Source::open(path).unwrap();
+        source.index_all().unwrap();
+        source.offsets
+    }
+
+    #[test]
+    fn offsets_after_replacing_match_a_fresh_index() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bookmarks.txt");
+        let cases = [
+            ("a\nb\nc\n", 1..2, lines(&["x", "yy"]), "a\nx\nyy\nc\n"),
+            ("a\nb\nc\n", 0..1, lines(&[]), "b\nc\n"),
+            ("a\nb\nc\n", 3..3, lines(&["d"]), "a\nb\nc\nd\n"),
+            ("a\nb\nc\n", 0..3, lines(&[]), ""),
+            ("", 0..0, lines(&["a"]), "a\n"),
+            // Without a final line break.
+            ("a\nb", 2..2, lines(&["c"]), "a\nb\nc\n"),
+            ("a\nb", 1..2, lines(&["z"]), "a\nz\n"),
+            ("a\nb", 0..1, lines(&[]), "b"),
+            ("a", 0..0, lines(&["new"]), "new\na"),
+            // Carriage returns are part of the lines.
+            ("a\r\nb\r\nc\r\n", 1..2, lines(&["x"]), "a\r\nx\nc\r\n"),
+            ("a\r\nb\r\n", 2..2, lines(&["c\r"]), "a\r\nb\r\nc\r\n"),
+        ];
+
+        for (text, range, new, expected) in cases {
+            fs::write(&path, text).unwrap();
+            let mut source = Source::open(&path).unwrap();
+            source.replace_lines(range.clone(), &new, false).unwrap();
+
+            let context = format!("{text:?} {range:?} {new:?}");
+            assert_eq!(fs::read_to_string(&path).unwrap(), expected, "{context}");
+            assert_eq!(source.offsets, fresh_offsets(&path), "{context}");
+            assert!(!source.changed_on_disk(), "{context}");
+            let count = source.index_all().unwrap();
+            let mut fresh = Source::open(&path).unwrap();
+            assert_eq!(
+                source.read_lines(0..count).unwrap(),
+                fresh.read_lines(0..count).unwrap(),
+                "{context}"
+            );
+        }
+    }
+
+    #[test]
+    fn replacing_past_the_end_fails() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bookmarks.txt");
+        fs::write(&path, "a\nb\n").unwrap();
+        let mut source = Source::open(&path).unwrap();
+
+        assert!(matches!(
+            source.replace_lines(3..3, &lines(&["c"]), false),
+            Err(Error::PastEnd(3))
+        ));
+        assert!(matches!(
+            source.replace_lines(1..3, &[], false),
+            Err(Error::PastEnd(3))
+        ));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "a\nb\n");
+    }
+
+    #[test]
+    fn lines_are_indexed_up_to_the_given_line() {
+        let mut reader = io::Cursor::new("a\n\nccc\nlast");
+        let mut offsets = vec![0];
+        assert!(!index_lines(&mut reader, &mut offsets, 2).unwrap());
+        assert_eq!(offsets, [0, 2, 3]);
+        // A last line without a line break ends at the end of the file.
+        assert!(index_lines(&mut reader, &mut offsets, usize::MAX).unwrap());
+        assert_eq!(offsets, [0, 2, 3, 7, 11]);
+
+        let mut offsets = vec![0];
+        assert!(index_lines(&mut io::Cursor::new(""), &mut offsets, usize::MAX).unwrap());
+        assert_eq!(offsets, [0]);
+    }
+
+    #[test]
+    fn empty_file_has_no_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bookmarks.txt");
+        fs::write(&path, "").unwrap();
+        let mut source = Source::open(&path).unwrap();
+
+        assert_eq!(source.index_all().unwrap(), 0);
+        assert_eq!(source.line_count(), Some(0));
+        assert!(source.read_lines(0..2).unwrap().is_empty());
+        assert_eq!(source.entries_from(0).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn entries_past_the_end_are_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bookmarks.txt");
+        fs::write(&path, "https://a\nhttps://b").unwrap();
+        let mut source = Source::open(&path).unwrap();
+
+        let urls = |source: &mut Source, line| {
+            source
+                .entries_from(line)
+                .unwrap()
+                .map(|entry| entry.unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(urls(&mut source, 1), ["https://b"]);
+        assert!(urls(&mut source, 2).is_empty());
+        assert!(urls(&mut source, 10).is_empty());
+        assert_eq!(urls(&mut source, 0), ["https://a", "https://b"]);
+        assert_eq!(source.read_lines(1..5).unwrap(), ["https://b"]);
+    }
+
+    /// Poll until the background index arrived, returns whether any poll changed the index.
+    fn wait_for_background(source: &mut Source) -> bool {
+        let mut changed = false;
+        while source.pending.is_some() {
+            changed |= source.poll_index().unwrap();
+            thread::yield_now();
+        }
+        changed
+    }
+
+    #[test]
+    fn background_index_is_taken_unless_already_complete() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bookmarks.txt");
+        fs::write(&path, "a\nb\nc\n").unwrap();
+
+        let mut source = Source::open(&path).unwrap();
+        source.index_to(1).unwrap();
+        assert_eq!(source.line_count(), None);
+        assert!(wait_for_background(&mut source));
+        assert_eq!(source.line_count(), Some(3));
+        assert_eq!(source.offsets, [0, 2, 4, 6]);
+
+        // The foreground index was completed first, the background one changes nothing.
+        let mut source = Source::open(&path).unwrap();
+        assert_eq!(source.index_all().unwrap(), 3);
+        let offsets = source.offsets.clone();
+        assert!(!wait_for_background(&mut source));
+        assert_eq!(source.offsets, offsets);
+        assert_eq!(source.line_count(), Some(3));
+    }
+}
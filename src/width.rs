@@ -0,0 +1,32 @@
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthChar;
+
+/// Variation selector requesting emoji presentation, which is drawn two cells wide.
+const EMOJI_PRESENTATION: char = '\u{FE0F}';
+
+/// Terminal cells taken up by a grapheme cluster.
+///
+/// The width of a cluster is given by its first character, combining marks and joined characters
+/// are drawn in the same cells. Control characters have no width.
+pub fn grapheme_width(grapheme: &str) -> usize {
+    let mut chars = grapheme.chars();
+    let width = chars.next().and_then(|c| c.width()).unwrap_or(0);
+
+    if width == 1 && chars.any(|c| c == EMOJI_PRESENTATION) {
+        2
+    } else {
+        width
+    }
+}
+
+/// Byte index at which the string stops fitting in the given amount of cells.
+pub fn fit_width(text: &str, max_width: usize) -> usize {
+    let mut used = 0;
+    for (i, grapheme) in text.grapheme_indices(true) {
+        used += grapheme_width(grapheme);
+        if used > max_width {
+            return i;
+        }
+    }
+    text.len()
+}